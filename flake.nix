@@ -44,6 +44,11 @@
               type = lib.types.str;
               description = "Url of the frame";
             };
+            timezone = lib.mkOption {
+              type = lib.types.str;
+              default = "Europe/London";
+              description = "IANA timezone to show times in";
+            };
             weather_lat = lib.mkOption {
               type = lib.types.float;
               description = "Latitude to get weather from";
@@ -79,6 +84,7 @@
               environment = {
                 PORT = toString config.services.inky-display.port;
                 FRAME_URL = toString config.services.inky-display.frame_url;
+                TIMEZONE = config.services.inky-display.timezone;
                 WEATHER_LAT = toString config.services.inky-display.weather_lat;
                 WEATHER_LONG = toString config.services.inky-display.weather_long;
                 FOOTBALL_API_KEY = toString config.services.inky-display.football_api_key;
@@ -1,18 +1,28 @@
 use anyhow::Result;
 use axum::Router;
+use axum::extract::DefaultBodyLimit;
 use axum::http::Request;
 use axum::routing::get;
+use axum::routing::patch;
 use axum::routing::post;
 use headless_chrome::Browser;
 use headless_chrome::LaunchOptions;
 use headless_chrome::browser::default_executable;
 use inky_display::AppError;
-use inky_display::SERVER_CONFIG;
 use inky_display::ServerAppState;
+use inky_display::ServerConfig;
 use inky_display::comm;
+use inky_display::custom_pages;
+use inky_display::images;
+use inky_display::message::{self, MessageBoard};
 use inky_display::page;
+use inky_display::photos::PhotoFrame;
+use inky_display::scheduler;
+use inky_display::scheduler::Scheduler;
 use reqwest::Client;
 use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::LatencyUnit;
@@ -38,33 +48,116 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let mut config_path = None;
+    let mut check_only = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check-config" => check_only = true,
+            "--config" => {
+                config_path = Some(PathBuf::from(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("--config needs a path"))?,
+                ));
+            }
+            _ => anyhow::bail!("Unknown argument: '{arg}'"),
+        }
+    }
+
+    let config = match ServerConfig::load(config_path.as_deref()) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprint!("{e}");
+            std::process::exit(1);
+        }
+    };
+    if check_only {
+        println!("Config is valid");
+        return Ok(());
+    }
+    config.log_summary();
+
     let launch_options = LaunchOptions::default_builder()
         .idle_browser_timeout(Duration::MAX)
         .path(Some(default_executable().map_err(|e| anyhow::anyhow!(e))?))
         .build()?;
 
+    let scheduler = config
+        .scheduler
+        .clone()
+        .map(|s| Scheduler::new(s, config.localization.tz))
+        .transpose()?;
+
     let state = ServerAppState {
         browser: Browser::new(launch_options)?,
-        client: Client::new(),
+        // shared by every data source, so one slow API can't hold up a page for long
+        client: Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .build()?,
+        config: config.clone(),
+        scheduler: scheduler.as_ref().map(Scheduler::handle),
+        photos: Arc::new(PhotoFrame::new(config.photos.clone())),
+        message: MessageBoard::default(),
     };
 
+    config.sources.spawn_refresh(&state.client);
+
+    if let Some(scheduler) = scheduler {
+        tracing::info!("Starting scheduler");
+        tokio::spawn(scheduler.run(state.clone()));
+    }
+
     let page_router = Router::new()
         .route("/large_text", get(page::large_text_handler))
         .route("/dashboard", get(page::dashboard_handler))
+        .route("/matchday", get(page::matchday_handler))
+        .route("/departures", get(page::departures_handler))
+        .route("/agenda", get(page::agenda_handler))
+        .route("/headlines", get(page::headlines_handler))
+        .route("/{name}", get(custom_pages::custom_page_handler))
         .with_state(state.clone());
 
     let controller_router = Router::new()
         .route("/check", get(comm::health_check))
         .route("/image/{image_path}", post(comm::set_to_image))
         .route("/page/{image_path}", post(comm::set_to_page))
+        .route("/url", post(comm::set_to_url))
+        .route("/photo", post(comm::set_to_photo))
         .route("/stripe", post(comm::set_to_stripes))
+        .route(
+            "/message",
+            get(message::get_message)
+                .post(message::post_message)
+                .delete(message::delete_message),
+        )
+        .route("/schedule", get(scheduler::status))
+        .route("/schedule/next", post(scheduler::skip))
+        .with_state(state.clone());
+
+    let images_router = Router::new()
+        .route(
+            "/",
+            get(images::list_images)
+                .post(images::upload_images)
+                // room for the rest of the multipart form, the image itself is checked later
+                .layer(DefaultBodyLimit::max(
+                    config.photos.max_upload_bytes().saturating_add(64 * 1024),
+                )),
+        )
+        .route("/thumbnail/{*name}", get(images::image_thumbnail))
+        .route(
+            "/{*name}",
+            patch(images::rename_image).delete(images::delete_image),
+        )
         .with_state(state.clone());
 
     let app = Router::new()
-        .nest_service("/static", ServeDir::new(&SERVER_CONFIG.static_root))
-        .nest_service("/image", ServeDir::new("./images"))
+        .nest_service("/static", ServeDir::new(&config.static_root))
+        .nest_service("/image", ServeDir::new(&config.photos.dir))
         .nest("/pages", page_router)
         .nest("/api/control", controller_router)
+        .nest("/api/images", images_router)
         .fallback(|| async { AppError::NotFound })
         .layer(
             ServiceBuilder::new().layer(
@@ -85,8 +178,7 @@ async fn main() -> Result<()> {
             ),
         );
 
-    let listener =
-        tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &SERVER_CONFIG.port)).await?;
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
     tracing::info!("listening on {}", listener.local_addr()?);
 
     axum::serve(listener, app)
@@ -0,0 +1,170 @@
+use crate::data_sources::{HourForecast, Units};
+use std::fmt::Write;
+
+// Colours the panel can show without dithering.
+const BLACK: &str = "#000000";
+const RED: &str = "#ff0000";
+const BLUE: &str = "#0000ff";
+
+/// Height reserved under the plot for the hour labels.
+const AXIS_HEIGHT: f32 = 14.0;
+/// Room above the plot for the temperature labels.
+const TOP_MARGIN: f32 = 12.0;
+
+/// Renders the hourly forecast as an SVG, with bars for the chance of rain, the amount in `units`
+/// over the wetter bars and a line for the temperature. Only uses colours from the panel's palette
+/// and no anti-aliased fills, so it stays crisp on the e-ink display.
+#[must_use]
+pub fn hourly_forecast(
+    hours: &[HourForecast],
+    units: Units,
+    width: u32,
+    height: u32,
+) -> Option<String> {
+    if hours.is_empty() {
+        return None;
+    }
+
+    let (width, height) = (width as f32, height as f32);
+    let plot_height = height - AXIS_HEIGHT - TOP_MARGIN;
+    let slot = width / hours.len() as f32;
+    let bottom = TOP_MARGIN + plot_height;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" shape-rendering="crispEdges" font-family="sans-serif" font-size="10" font-weight="bold">"#
+    );
+
+    // chance of rain
+    for (i, hour) in hours.iter().enumerate() {
+        let bar = plot_height * f32::from(hour.precipitation_probability.min(100)) / 100.0;
+        let x = i as f32 * slot + 1.0;
+        let _ = write!(
+            svg,
+            r#"<rect x="{x:.1}" y="{:.1}" width="{:.1}" height="{bar:.1}" fill="{BLUE}"/>"#,
+            bottom - bar,
+            (slot - 2.0).max(1.0),
+        );
+        if hour.precipitation >= units.notable_precipitation() {
+            let _ = write!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" font-size="8" fill="{BLUE}">{:.*}</text>"#,
+                x + slot / 2.0 - 1.0,
+                (bottom - bar - 2.0).max(TOP_MARGIN),
+                units.precipitation_decimals(),
+                hour.precipitation,
+            );
+        }
+    }
+
+    // baseline and hour labels every three hours
+    let _ = write!(
+        svg,
+        r#"<line x1="0" y1="{bottom:.1}" x2="{width}" y2="{bottom:.1}" stroke="{BLACK}" stroke-width="1"/>"#
+    );
+    for (i, hour) in hours.iter().enumerate().step_by(3) {
+        let _ = write!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" fill="{BLACK}">{}</text>"#,
+            i as f32 * slot + slot / 2.0,
+            height - 2.0,
+            hour.hour,
+        );
+    }
+
+    // temperature, scaled to the plot with a little headroom
+    let (min, max) = hours
+        .iter()
+        .map(|h| h.temperature)
+        .fold((f32::MAX, f32::MIN), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    let range = (max - min).max(4.0);
+    let y = |t: f32| bottom - (t - min + range * 0.1) / (range * 1.2) * plot_height;
+    let points: Vec<String> = hours
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            format!(
+                "{:.1},{:.1}",
+                i as f32 * slot + slot / 2.0,
+                y(h.temperature)
+            )
+        })
+        .collect();
+    let _ = write!(
+        svg,
+        r#"<polyline points="{}" fill="none" stroke="{RED}" stroke-width="2.5" stroke-linejoin="round"/>"#,
+        points.join(" "),
+    );
+
+    // label the warmest and coldest hours
+    let extremes = [
+        hours.iter().position(|h| h.temperature == max),
+        hours.iter().position(|h| h.temperature == min),
+    ];
+    for i in extremes.into_iter().flatten() {
+        let t = hours[i].temperature;
+        let x = (i as f32 * slot + slot / 2.0).clamp(10.0, width - 10.0);
+        let _ = write!(
+            svg,
+            r#"<text x="{x:.1}" y="{:.1}" text-anchor="middle" fill="{RED}">{:.0}°</text>"#,
+            (y(t) - 4.0).max(10.0),
+            t.round(),
+        );
+    }
+
+    svg.push_str("</svg>");
+    Some(svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hour(hour: u32, temperature: f32, probability: u8, amount: f32) -> HourForecast {
+        HourForecast {
+            hour: format!("{hour:02}"),
+            temperature,
+            precipitation_probability: probability,
+            precipitation: amount,
+        }
+    }
+
+    #[test]
+    fn draws_bars_line_and_labels() {
+        let hours: Vec<_> = (0..12)
+            .map(|i| {
+                hour(
+                    i + 9,
+                    12.0 + i as f32,
+                    (i * 10) as u8,
+                    if i == 6 { 2.4 } else { 0.0 },
+                )
+            })
+            .collect();
+        let svg = hourly_forecast(&hours, Units::Metric, 360, 90).unwrap();
+
+        assert_eq!(svg.matches("<rect").count(), 12);
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        // nothing opts back into anti-aliasing
+        assert_eq!(svg.matches("shape-rendering").count(), 1);
+        // hours 09, 12, 15 and 18, one rain amount and the max and min temperatures
+        assert_eq!(svg.matches("<text").count(), 4 + 1 + 2);
+        assert!(svg.contains(">23°<") && svg.contains(">12°<"));
+        for colour in [BLACK, RED, BLUE] {
+            assert!(svg.contains(colour));
+        }
+    }
+
+    #[test]
+    fn labels_rain_in_inches() {
+        let hours = [hour(9, 40.0, 80, 0.04), hour(10, 41.0, 20, 0.01)];
+        let svg = hourly_forecast(&hours, Units::Imperial, 360, 90).unwrap();
+
+        assert!(svg.contains(">0.04<"));
+        assert!(!svg.contains(">0.01<"));
+    }
+
+    #[test]
+    fn nothing_to_draw_without_hours() {
+        assert!(hourly_forecast(&[], Units::Metric, 360, 90).is_none());
+    }
+}
@@ -1,126 +1,409 @@
 use crate::controller::Inky;
-use crate::{AppError, SERVER_CONFIG, ServerAppState, pad_and_convert};
+use crate::photos::{Fit, ImageSettings, Photo};
+use crate::{AppError, ServerAppState, pad_and_convert};
 use anyhow::{Context, Result};
+use axum::Json;
 use axum::debug_handler;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
+use headless_chrome::protocol::cdp::Network::CookieParam;
 use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
 use headless_chrome::protocol::cdp::Page::SetDeviceMetricsOverride;
 use headless_chrome::protocol::cdp::Target::CreateTarget;
-use image::{ImageReader, load_from_memory_with_format};
-use reqwest::Client;
+use headless_chrome::{Browser, Tab};
+use image::{DynamicImage, RgbImage, load_from_memory_with_format};
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
 
 #[debug_handler]
-pub async fn health_check(State(client): State<Client>) -> Result<StatusCode, AppError> {
-    let res = client
-        .get(format!("{}/api/control/check", &SERVER_CONFIG.frame_url))
+pub async fn health_check(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
+    let res = state
+        .client
+        .get(format!("{}/api/control/check", state.config.frame_url))
         .send()
         .await?;
 
     Ok(res.error_for_status()?.status())
 }
 
+/// Decodes and fits the photo off the async runtime, then sends it with its settings.
+async fn show_photo(state: &ServerAppState, photo: Photo) -> Result<StatusCode, AppError> {
+    let ImageSettings {
+        fit,
+        dither,
+        saturation,
+    } = photo.settings;
+    let image = tokio::task::spawn_blocking(move || photo.decode().map(|i| fit.apply(&i)))
+        .await
+        .context("Image task panicked")??;
+
+    let b = pad_and_convert(&image)?;
+    send_to_frame_with(state, b, dither, saturation).await
+}
+
+/// Displays a file from the photos directory on the frame.
+pub async fn display_image(
+    state: &ServerAppState,
+    image_path: &str,
+) -> Result<StatusCode, AppError> {
+    show_photo(state, state.photos.photo(image_path)?).await
+}
+
+/// Displays the photo frame's next image.
+pub async fn display_photo(state: &ServerAppState) -> Result<StatusCode, AppError> {
+    let photos = state.photos.clone();
+    let today = state.config.localization.today();
+    let photo = tokio::task::spawn_blocking(move || photos.next(today))
+        .await
+        .context("Photo task panicked")??;
+    tracing::info!("Showing photo: '{}'", photo.path.display());
+    show_photo(state, photo).await
+}
+
+/// Renders one of the server's own pages and displays it on the frame.
+pub async fn display_page(state: &ServerAppState, page_path: &str) -> Result<StatusCode, AppError> {
+    display_url(
+        state,
+        RenderUrlRequest::for_url(format!(
+            "http://localhost:{}/pages/{page_path}",
+            state.config.port
+        )),
+    )
+    .await
+}
+
+/// Renders an arbitrary URL and displays it on the frame.
+pub async fn display_url(
+    state: &ServerAppState,
+    request: RenderUrlRequest,
+) -> Result<StatusCode, AppError> {
+    request.validate()?;
+    tracing::info!("Rendering url: '{}'", request.url);
+    let image = render_url_blocking(state.browser.clone(), request).await?;
+
+    let b = pad_and_convert(&image)?;
+    send_to_frame(state, b).await
+}
+
 #[debug_handler]
 pub async fn set_to_image(
-    State(client): State<Client>,
+    State(state): State<ServerAppState>,
     Path(image_path): Path<String>,
 ) -> Result<StatusCode, AppError> {
-    let image_path = format!("./images/{image_path}");
-    let image = ImageReader::open(&image_path)
-        .map_err(|e| {
-            tracing::info!("Error reading image file at '{}': '{}'", image_path, e);
-            AppError::InvalidInput("Unable to find image".into())
-        })?
-        .decode()
-        .context("Couldn't decode image")?
-        .resize(
-            Inky::WIDTH as u32,
-            Inky::HEIGHT as u32,
-            image::imageops::FilterType::Lanczos3,
-        )
-        .to_rgb8();
-
-    let b = pad_and_convert(&image)?;
-    let res = client
-        .post(format!("{}/api/control/set", &SERVER_CONFIG.frame_url))
-        .body(b)
-        .send()
-        .await?;
+    display_image(&state, &image_path).await
+}
 
-    Ok(res.error_for_status()?.status())
+#[debug_handler]
+pub async fn set_to_photo(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
+    display_photo(&state).await
 }
 
-#[allow(clippy::unused_async)]
+#[debug_handler]
 pub async fn set_to_page(
     State(state): State<ServerAppState>,
     Path(page_path): Path<String>,
 ) -> Result<StatusCode, AppError> {
-    let image = {
-        let tab = state.browser.new_tab_with_options(CreateTarget {
-            url: "about:blank".to_string(),
-            width: Some(Inky::WIDTH as u32),
-            height: Some(Inky::HEIGHT as u32),
-            browser_context_id: None,
-            enable_begin_frame_control: None,
-            new_window: Some(true),
-            background: None,
-            for_tab: None,
-        })?;
+    display_page(&state, &page_path).await
+}
 
-        tab.navigate_to(&format!(
-            "http://localhost:{}/pages/{page_path}",
-            &SERVER_CONFIG.port
-        ))?;
-        tab.wait_until_navigated()?;
-        tab.wait_for_element("body")?;
-        tab.call_method(SetDeviceMetricsOverride {
-            width: Inky::WIDTH as u32,
-            height: Inky::HEIGHT as u32,
-            device_scale_factor: 1.0,
-            mobile: false,
-            scale: Some(1.0),
-            screen_width: Some(Inky::WIDTH as u32),
-            screen_height: Some(Inky::HEIGHT as u32),
-            position_x: Some(0),
-            position_y: Some(0),
-            dont_set_visible_size: Some(false),
-            screen_orientation: None,
-            viewport: None,
+#[debug_handler]
+pub async fn set_to_url(
+    State(state): State<ServerAppState>,
+    Json(request): Json<RenderUrlRequest>,
+) -> Result<StatusCode, AppError> {
+    display_url(&state, request).await
+}
+
+#[debug_handler]
+pub async fn set_to_stripes(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
+    let res = state
+        .client
+        .get(format!("{}/api/control/stripe", state.config.frame_url))
+        .send()
+        .await?;
+
+    Ok(res.error_for_status()?.status())
+}
+
+/// Largest viewport width or height a URL can be rendered at.
+const MAX_VIEWPORT: u32 = 4096;
+/// Longest a render can wait for the page to settle, so a request can't hold the browser.
+const MAX_WAIT_MS: u64 = 30_000;
+
+/// Options for rendering an arbitrary URL to the frame.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderUrlRequest {
+    pub url: String,
+    /// CSS selector of the element to crop the screenshot to.
+    #[serde(default)]
+    pub selector: Option<String>,
+    /// Viewport width, defaults to the panel width.
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Viewport height, defaults to the panel height.
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Extra CSS injected into the page once loaded, e.g. to hide headers.
+    #[serde(default)]
+    pub css: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub cookies: Vec<RenderCookie>,
+    /// Extra time to wait after the page loads before the screenshot, for pages that draw
+    /// asynchronously.
+    #[serde(default)]
+    pub wait_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderCookie {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl RenderUrlRequest {
+    fn for_url(url: String) -> Self {
+        Self {
+            url,
+            selector: None,
+            width: None,
+            height: None,
+            css: None,
+            headers: HashMap::new(),
+            cookies: Vec::new(),
+            wait_ms: None,
+        }
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        let url = Url::parse(&self.url).map_err(|e| {
+            AppError::InvalidInput(format!("Invalid url '{}': {e}", self.url).into())
         })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::InvalidInput(
+                format!("Unsupported url scheme: '{}'", url.scheme()).into(),
+            ));
+        }
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err(AppError::InvalidInput(
+                "Viewport dimensions must be non-zero".into(),
+            ));
+        }
+        if self.width.max(self.height) > Some(MAX_VIEWPORT) {
+            return Err(AppError::InvalidInput(
+                format!("Viewport dimensions can be at most {MAX_VIEWPORT}").into(),
+            ));
+        }
+        if self.wait_ms > Some(MAX_WAIT_MS) {
+            return Err(AppError::InvalidInput(
+                format!("wait_ms can be at most {MAX_WAIT_MS}").into(),
+            ));
+        }
+        Ok(())
+    }
+}
 
-        let element = tab.find_element("body")?;
-        element.scroll_into_view()?;
+/// Scales an image to fit within the panel, keeping its aspect ratio.
+fn fit_to_panel(image: &DynamicImage) -> RgbImage {
+    Fit::Contain.apply(image)
+}
 
-        let screenshot =
-            tab.capture_screenshot(CaptureScreenshotFormatOption::Png, Some(100), None, false)?;
+/// Loads the URL in a new browser tab and screenshots it.
+fn render_url(browser: &Browser, request: &RenderUrlRequest) -> Result<RgbImage, AppError> {
+    let width = request.width.unwrap_or(Inky::WIDTH as u32);
+    let height = request.height.unwrap_or(Inky::HEIGHT as u32);
 
-        tab.close_with_unload()?;
+    let tab = browser.new_tab_with_options(CreateTarget {
+        url: "about:blank".to_string(),
+        width: Some(width),
+        height: Some(height),
+        browser_context_id: None,
+        enable_begin_frame_control: None,
+        new_window: Some(true),
+        background: None,
+        for_tab: None,
+    })?;
+    // the browser is shared and long-lived, so close the tab however the capture went
+    let image = capture_tab(&tab, request, width, height);
+    let closed = tab.close_with_unload();
+    let image = image?;
+    closed?;
 
-        screenshot
-    };
     let image = load_from_memory_with_format(&image, image::ImageFormat::Png)
-        .expect("screenshot wasnt a png")
-        .to_rgb8();
+        .context("Screenshot wasn't a png")?;
 
-    let b = pad_and_convert(&image)?;
+    Ok(fit_to_panel(&image))
+}
 
-    let res = state
-        .client
-        .post(format!("{}/api/control/set", &SERVER_CONFIG.frame_url))
-        .body(b)
-        .send()
-        .await?;
+/// Loads the request's URL in `tab` and screenshots it as a png.
+fn capture_tab(
+    tab: &Tab,
+    request: &RenderUrlRequest,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, AppError> {
+    if !request.headers.is_empty() {
+        tab.set_extra_http_headers(
+            request
+                .headers
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect(),
+        )?;
+    }
 
-    Ok(res.error_for_status()?.status())
+    if !request.cookies.is_empty() {
+        tab.set_cookies(
+            request
+                .cookies
+                .iter()
+                .map(|c| CookieParam {
+                    name: c.name.clone(),
+                    value: c.value.clone(),
+                    url: c.domain.is_none().then(|| request.url.clone()),
+                    domain: c.domain.clone(),
+                    path: c.path.clone(),
+                    secure: None,
+                    http_only: None,
+                    same_site: None,
+                    expires: None,
+                    priority: None,
+                    same_party: None,
+                    source_scheme: None,
+                    source_port: None,
+                    partition_key: None,
+                })
+                .collect(),
+        )?;
+    }
+
+    tab.navigate_to(&request.url)?;
+    tab.wait_until_navigated()?;
+    tab.wait_for_element("body")?;
+    tab.call_method(SetDeviceMetricsOverride {
+        width,
+        height,
+        device_scale_factor: 1.0,
+        mobile: false,
+        scale: Some(1.0),
+        screen_width: Some(width),
+        screen_height: Some(height),
+        position_x: Some(0),
+        position_y: Some(0),
+        dont_set_visible_size: Some(false),
+        screen_orientation: None,
+        viewport: None,
+    })?;
+
+    if let Some(css) = &request.css {
+        let css = serde_json::to_string(css).context("Couldn't encode injected css")?;
+        tab.evaluate(
+            &format!(
+                "(() => {{ const s = document.createElement('style'); s.textContent = {css}; document.head.appendChild(s); }})()"
+            ),
+            false,
+        )?;
+    }
+
+    if let Some(wait_ms) = request.wait_ms {
+        std::thread::sleep(std::time::Duration::from_millis(wait_ms));
+    }
+
+    let screenshot = if let Some(selector) = &request.selector {
+        let element = tab.wait_for_element(selector).map_err(|e| {
+            tracing::info!("Couldn't find element '{}': '{}'", selector, e);
+            AppError::InvalidInput(format!("No element matches selector '{selector}'").into())
+        })?;
+        element.scroll_into_view()?;
+        let viewport = element.get_box_model()?.margin_viewport();
+        tab.capture_screenshot(
+            CaptureScreenshotFormatOption::Png,
+            Some(100),
+            Some(viewport),
+            false,
+        )?
+    } else {
+        let element = tab.find_element("body")?;
+        element.scroll_into_view()?;
+        tab.capture_screenshot(CaptureScreenshotFormatOption::Png, Some(100), None, false)?
+    };
+
+    Ok(screenshot)
 }
 
-#[debug_handler]
-pub async fn set_to_stripes(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
-    let res = state
-        .client
-        .get(format!("{}/api/control/stripe", &SERVER_CONFIG.frame_url))
-        .send()
-        .await?;
+/// Renders the URL off the async runtime, as the browser calls block.
+async fn render_url_blocking(
+    browser: Browser,
+    request: RenderUrlRequest,
+) -> Result<RgbImage, AppError> {
+    tokio::task::spawn_blocking(move || render_url(&browser, &request))
+        .await
+        .context("Render task panicked")?
+}
+
+/// Sends an already padded png to the frame.
+async fn send_to_frame(state: &ServerAppState, png: Vec<u8>) -> Result<StatusCode, AppError> {
+    send_to_frame_with(state, png, true, None).await
+}
+
+/// Sends an already padded png to the frame, choosing whether it is dithered and optionally
+/// overriding the frame's saturation for this image.
+async fn send_to_frame_with(
+    state: &ServerAppState,
+    png: Vec<u8>,
+    dither: bool,
+    saturation: Option<f32>,
+) -> Result<StatusCode, AppError> {
+    let mut url = Url::parse(&format!("{}/api/control/set", state.config.frame_url))
+        .context("Invalid frame url")?;
+    url.query_pairs_mut()
+        .append_pair("dither", &dither.to_string());
+    if let Some(saturation) = saturation {
+        url.query_pairs_mut()
+            .append_pair("saturation", &saturation.to_string());
+    }
+    let res = state.client.post(url).body(png).send().await?;
 
     Ok(res.error_for_status()?.status())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(json: serde_json::Value) -> RenderUrlRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn validates_render_requests() {
+        assert!(
+            request(serde_json::json!({ "url": "https://grafana.local/d/abc", "width": 1200, "wait_ms": 2000 }))
+                .validate()
+                .is_ok()
+        );
+
+        for bad in [
+            serde_json::json!({ "url": "file:///etc/passwd" }),
+            serde_json::json!({ "url": "not a url" }),
+            serde_json::json!({ "url": "http://ha.local", "width": 0 }),
+            serde_json::json!({ "url": "http://ha.local", "height": 0 }),
+            serde_json::json!({ "url": "http://ha.local", "width": 100_000 }),
+            serde_json::json!({ "url": "http://ha.local", "wait_ms": 600_000 }),
+        ] {
+            assert!(
+                matches!(
+                    request(bad.clone()).validate(),
+                    Err(AppError::InvalidInput(_))
+                ),
+                "{bad}"
+            );
+        }
+    }
+}
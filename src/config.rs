@@ -0,0 +1,643 @@
+use crate::dashboard::DashboardConfig;
+use crate::data_sources::{FootballSource, Registry};
+use crate::photos::PhotosConfig;
+use crate::scheduler::ScheduleConfig;
+use crate::time::{Localization, deserialize_locale, deserialize_tz};
+use anyhow::{Context, anyhow};
+use chrono::Locale;
+use chrono_tz::Tz;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use toml::{Table, Value};
+
+const DEFAULT_CONFIG_PATH: &str = "./config.toml";
+
+/// Suffix for keys and environment variables that name a file holding the actual value.
+const FILE_SUFFIX: &str = "_file";
+
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    Str,
+    Int,
+    Float,
+}
+
+/// Environment variables that override values from the config file, and where they go.
+/// Each can also be given as `<NAME>_FILE` pointing at a file containing the value.
+const ENV_OVERRIDES: &[(&str, &[&str], EnvKind)] = &[
+    ("PORT", &["port"], EnvKind::Int),
+    ("FRAME_URL", &["frame_url"], EnvKind::Str),
+    ("STATIC_ROOT", &["static_root"], EnvKind::Str),
+    ("TIMEZONE", &["timezone"], EnvKind::Str),
+    ("LOCALE", &["locale"], EnvKind::Str),
+    ("TEMPLATES_DIR", &["templates_dir"], EnvKind::Str),
+    ("WEATHER_LAT", &["weather", "lat"], EnvKind::Float),
+    ("WEATHER_LONG", &["weather", "long"], EnvKind::Float),
+    (
+        "MET_OFFICE_API_KEY",
+        &["weather", "met_office", "api_key"],
+        EnvKind::Str,
+    ),
+    (
+        "OPENWEATHERMAP_API_KEY",
+        &["weather", "openweathermap", "api_key"],
+        EnvKind::Str,
+    ),
+    ("FOOTBALL_API_KEY", &["football", "api_key"], EnvKind::Str),
+    ("TUBE_API_KEY", &["tube", "api_key"], EnvKind::Str),
+];
+
+/// Every problem found while loading the config, so they can all be fixed in one go.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Found {} problem(s) in the config:", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn default_port() -> u16 {
+    8080
+}
+
+fn default_static_root() -> String {
+    "./static".to_string()
+}
+
+fn default_timezone() -> Tz {
+    Localization::default().tz
+}
+
+fn default_locale() -> Locale {
+    Localization::default().locale
+}
+
+#[derive(Debug, Deserialize)]
+struct CoreConfig {
+    #[serde(default = "default_port")]
+    port: u16,
+    frame_url: String,
+    #[serde(default = "default_static_root")]
+    static_root: String,
+    /// IANA timezone name, e.g. `Europe/London`.
+    #[serde(default = "default_timezone", deserialize_with = "deserialize_tz")]
+    timezone: Tz,
+    /// Locale for day and month names, e.g. `en_GB`.
+    #[serde(default = "default_locale", deserialize_with = "deserialize_locale")]
+    locale: Locale,
+    /// Folder of extra page templates, each served at `/pages/<name>`.
+    #[serde(default)]
+    templates_dir: Option<PathBuf>,
+}
+
+/// Server configuration. Any other section configures the data source of the same name, and
+/// sources whose section is missing are disabled.
+#[derive(Debug)]
+pub struct ServerConfig {
+    pub port: u16,
+    pub frame_url: String,
+    pub static_root: String,
+    pub localization: Localization,
+    pub scheduler: Option<ScheduleConfig>,
+    pub photos: PhotosConfig,
+    pub dashboard: DashboardConfig,
+    pub templates_dir: Option<PathBuf>,
+
+    pub sources: Arc<Registry>,
+}
+
+impl ServerConfig {
+    /// Loads the config file, falling back to the `CONFIG_FILE` environment variable and then
+    /// `./config.toml`. A missing default file is fine as long as the environment covers the
+    /// required settings.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (path, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match std::env::var("CONFIG_FILE") {
+                Ok(p) => (PathBuf::from(p), true),
+                Err(_) => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+            },
+        };
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!("No config file at '{}', using env only", path.display());
+                String::new()
+            }
+            Err(e) => {
+                return Err(ConfigError {
+                    problems: vec![format!("Couldn't read '{}': {e}", path.display())],
+                });
+            }
+        };
+
+        Self::from_toml(&contents, |name| std::env::var(name).ok())
+    }
+
+    /// Builds the config from a TOML document, applying overrides from `env`.
+    pub fn from_toml(
+        contents: &str,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+
+        let mut table: Table = contents.parse().map_err(|e| ConfigError {
+            problems: vec![format!("Invalid TOML: {e}")],
+        })?;
+        apply_env_overrides(&mut table, &env, &mut problems);
+        resolve_file_keys(&mut table, "", &mut problems);
+
+        let core: Option<CoreConfig> = deserialize_section(None, Value::Table(table.clone()))
+            .map_err(|e| problems.push(e))
+            .ok();
+        let scheduler = section::<ScheduleConfig>(&table, "scheduler", &mut problems);
+        let photos = section::<PhotosConfig>(&table, "photos", &mut problems).unwrap_or_default();
+        problems.extend(
+            photos
+                .validate()
+                .into_iter()
+                .map(|p| format!("photos: {p}")),
+        );
+        let dashboard =
+            section::<DashboardConfig>(&table, "dashboard", &mut problems).unwrap_or_default();
+        problems.extend(
+            dashboard
+                .validate()
+                .into_iter()
+                .map(|p| format!("dashboard: {p}")),
+        );
+
+        let mut source_sections = Table::new();
+        for (key, value) in &table {
+            match key.as_str() {
+                "port" | "frame_url" | "static_root" | "timezone" | "locale" | "templates_dir"
+                | "scheduler" | "photos" | "dashboard" => {}
+                _ if value.is_table() => {
+                    source_sections.insert(key.clone(), value.clone());
+                }
+                _ => problems.push(format!("Unknown setting '{key}'")),
+            }
+        }
+        let localization = core
+            .as_ref()
+            .map(|core| Localization {
+                tz: core.timezone,
+                locale: core.locale,
+            })
+            .unwrap_or_default();
+        let sources = Registry::from_sections(&source_sections, localization, &mut problems);
+
+        if let Some(core) = &core {
+            if core.port == 0 {
+                problems.push("port: must not be 0".to_string());
+            }
+            match Url::parse(&core.frame_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                Ok(_) => problems.push(format!(
+                    "frame_url: expected an http(s) url, got '{}'",
+                    core.frame_url
+                )),
+                Err(e) => problems.push(format!("frame_url: '{}' {e}", core.frame_url)),
+            }
+            if let Some(dir) = &core.templates_dir
+                && !dir.is_dir()
+            {
+                problems.push(format!(
+                    "templates_dir: '{}' is not a directory",
+                    dir.display()
+                ));
+            }
+        }
+        if let Some(scheduler) = &scheduler {
+            problems.extend(
+                scheduler
+                    .validate()
+                    .into_iter()
+                    .map(|p| format!("scheduler: {p}")),
+            );
+            if scheduler.match_day.is_some() && !sources.is_enabled::<FootballSource>() {
+                problems.push(
+                    "scheduler: match_day needs the [football] section to be configured"
+                        .to_string(),
+                );
+            }
+        }
+
+        match core {
+            Some(core) if problems.is_empty() => Ok(Self {
+                port: core.port,
+                frame_url: core.frame_url,
+                static_root: core.static_root,
+                localization,
+                scheduler,
+                photos,
+                dashboard,
+                templates_dir: core.templates_dir,
+                sources: Arc::new(sources),
+            }),
+            _ => Err(ConfigError { problems }),
+        }
+    }
+
+    /// Logs a summary of the loaded config, leaving out secrets.
+    pub fn log_summary(&self) {
+        tracing::debug!("Connecting to frame at: '{}'", self.frame_url);
+        tracing::debug!("Using static folder: '{}'", self.static_root);
+        tracing::debug!(
+            "Showing times in {} with locale {}",
+            self.localization.tz,
+            self.localization.locale
+        );
+        tracing::debug!("Showing photos from: '{}'", self.photos.dir.display());
+        if let Some(dir) = &self.templates_dir {
+            tracing::debug!("Serving page templates from: '{}'", dir.display());
+        }
+        tracing::debug!("Enabled data sources: {:?}", self.sources);
+        if self.scheduler.is_none() {
+            tracing::info!("No [scheduler] section configured, it is disabled");
+        }
+    }
+}
+
+/// Parses durations such as `90s`, `15m`, `2h` or `1h30m`.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let mut total = 0;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let value: u64 = digits
+            .parse()
+            .with_context(|| format!("Invalid duration: '{s}'"))?;
+        digits.clear();
+        let unit: u64 = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 60 * 60 * 24,
+            _ => return Err(anyhow!("Unknown duration unit '{c}' in '{s}'")),
+        };
+        total = value
+            .checked_mul(unit)
+            .and_then(|secs| secs.checked_add(total))
+            .ok_or_else(|| anyhow!("Duration '{s}' is too long"))?;
+    }
+
+    if !digits.is_empty() || total == 0 {
+        return Err(anyhow!(
+            "Invalid duration '{s}', expected something like '15m'"
+        ));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+pub(crate) fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_duration(&s).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_section<T: DeserializeOwned>(name: Option<&str>, value: Value) -> Result<T, String> {
+    value.try_into().map_err(|e: toml::de::Error| match name {
+        Some(name) => format!("{name}: {}", e.message()),
+        None => e.message().to_string(),
+    })
+}
+
+fn section<T: DeserializeOwned>(
+    table: &Table,
+    name: &str,
+    problems: &mut Vec<String>,
+) -> Option<T> {
+    let value = table.get(name)?.clone();
+    deserialize_section(Some(name), value)
+        .map_err(|e| problems.push(e))
+        .ok()
+}
+
+fn apply_env_overrides(
+    table: &mut Table,
+    env: &impl Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    for &(name, path, kind) in ENV_OVERRIDES {
+        let file_var = format!("{name}{}", FILE_SUFFIX.to_uppercase());
+        let raw = match (env(name), env(&file_var)) {
+            (Some(value), _) => value,
+            (None, Some(file)) => match read_secret(&file) {
+                Ok(value) => value,
+                Err(e) => {
+                    problems.push(format!("{file_var}: {e}"));
+                    continue;
+                }
+            },
+            (None, None) => continue,
+        };
+
+        let value = match kind {
+            EnvKind::Str => Ok(Value::String(raw.clone())),
+            EnvKind::Int => raw
+                .trim()
+                .parse()
+                .map(Value::Integer)
+                .map_err(|e| e.to_string()),
+            EnvKind::Float => raw
+                .trim()
+                .parse()
+                .map(Value::Float)
+                .map_err(|e| e.to_string()),
+        };
+
+        match value {
+            Ok(value) => set_path(table, name, path, value, problems),
+            Err(e) => problems.push(format!("{name}: couldn't parse '{raw}': {e}")),
+        }
+    }
+}
+
+/// Sets the value from the environment variable `name` at `path`, creating missing sections.
+fn set_path(
+    table: &mut Table,
+    name: &str,
+    path: &[&str],
+    value: Value,
+    problems: &mut Vec<String>,
+) {
+    let (last, parents) = path.split_last().expect("override paths are not empty");
+    let mut current = table;
+    for (i, key) in parents.iter().enumerate() {
+        let Some(section) = current
+            .entry(*key)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+        else {
+            problems.push(format!(
+                "{name}: '{}' is not a table",
+                parents[..=i].join(".")
+            ));
+            return;
+        };
+        current = section;
+    }
+    current.insert((*last).to_string(), value);
+}
+
+/// Replaces `<key>_file = "path"` entries with `<key> = "<file contents>"`, recursively.
+fn resolve_file_keys(table: &mut Table, prefix: &str, problems: &mut Vec<String>) {
+    let file_keys: Vec<String> = table
+        .iter()
+        .filter(|(k, v)| k.ends_with(FILE_SUFFIX) && v.is_str())
+        .map(|(k, _)| k.clone())
+        .collect();
+
+    for file_key in file_keys {
+        let key = file_key.trim_end_matches(FILE_SUFFIX).to_string();
+        let Some(Value::String(file)) = table.remove(&file_key) else {
+            continue;
+        };
+        if table.contains_key(&key) {
+            // a value set directly, e.g. from the environment, wins
+            continue;
+        }
+        match read_secret(&file) {
+            Ok(value) => {
+                table.insert(key, Value::String(value));
+            }
+            Err(e) => problems.push(format!("{prefix}{file_key}: {e}")),
+        }
+    }
+
+    for (key, value) in table.iter_mut() {
+        if let Value::Table(inner) = value {
+            resolve_file_keys(inner, &format!("{prefix}{key}."), problems);
+        }
+    }
+}
+
+fn read_secret(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|s| s.trim_end().to_string())
+        .map_err(|e| format!("couldn't read '{path}': {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::{
+        AirQualitySource, BikesSource, FootballSource, StandingsSource, TubeSource, WeatherSource,
+    };
+    use std::collections::HashMap;
+
+    fn load(contents: &str, env: &[(&str, &str)]) -> Result<ServerConfig, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        ServerConfig::from_toml(contents, |name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            Duration::from_secs(90 * 60)
+        );
+        assert!(parse_duration("15").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("100000000000000000d").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+    }
+
+    #[test]
+    fn missing_sections_disable_sources() {
+        let config = load(r#"frame_url = "http://frame:8080""#, &[]).unwrap();
+        assert_eq!(config.port, 8080);
+        assert!(!config.sources.is_enabled::<TubeSource>());
+        assert!(!config.sources.is_enabled::<WeatherSource>());
+        assert!(!config.sources.is_enabled::<FootballSource>());
+    }
+
+    #[test]
+    fn standings_share_the_football_api_key() {
+        let config = load(
+            r#"
+            frame_url = "http://frame:8080"
+            [football_standings]
+            competition = "PL"
+            team_id = 57
+            "#,
+            &[("FOOTBALL_API_KEY", "key")],
+        )
+        .unwrap();
+        assert!(config.sources.is_enabled::<StandingsSource>());
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let config = load(
+            r#"
+            frame_url = "http://frame:8080"
+            port = 80
+            "#,
+            &[("TUBE_API_KEY", "from-env"), ("PORT", "3000")],
+        )
+        .unwrap();
+        assert_eq!(config.port, 3000);
+        assert!(config.sources.is_enabled::<TubeSource>());
+
+        // the sources keep their config to themselves, so check the resolved keys in the table
+        let path = std::env::temp_dir().join("inky_display_config_test_env_secret");
+        std::fs::write(&path, "from-env-file\n").unwrap();
+        let env: HashMap<&str, String> = [
+            ("TUBE_API_KEY", "from-env".to_string()),
+            ("FOOTBALL_API_KEY_FILE", path.display().to_string()),
+        ]
+        .into();
+        let mut table: Table = r#"
+            [tube]
+            api_key = "from-file"
+            "#
+        .parse()
+        .unwrap();
+        let mut problems = Vec::new();
+        apply_env_overrides(&mut table, &|name| env.get(name).cloned(), &mut problems);
+
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(table["tube"]["api_key"].as_str(), Some("from-env"));
+        assert_eq!(table["football"]["api_key"].as_str(), Some("from-env-file"));
+    }
+
+    #[test]
+    fn reads_secrets_from_files() {
+        let path = std::env::temp_dir().join("inky_display_config_test_secret");
+        std::fs::write(&path, "secret-key\n").unwrap();
+        let mut table: Table = format!(
+            r#"
+            [football]
+            api_key_file = "{}"
+            "#,
+            path.display()
+        )
+        .parse()
+        .unwrap();
+        let mut problems = Vec::new();
+        resolve_file_keys(&mut table, "", &mut problems);
+
+        assert!(problems.is_empty(), "{problems:?}");
+        assert_eq!(table["football"]["api_key"].as_str(), Some("secret-key"));
+        assert!(table["football"].get("api_key_file").is_none());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let err = load(
+            r#"
+            frame_url = "not a url"
+            colour = "red"
+            [weather]
+            lat = 123.0
+            long = 0.0
+            [tube]
+            "#,
+            &[("PORT", "eighty")],
+        )
+        .unwrap_err();
+        assert_eq!(err.problems.len(), 5, "{err}");
+
+        // overrides into a section that isn't a table are reported, not a panic
+        let err = load(
+            r#"
+            frame_url = "http://frame:8080"
+            tube = 1
+            weather = 1
+            "#,
+            &[("TUBE_API_KEY", "key"), ("WEATHER_LAT", "51.5")],
+        )
+        .unwrap_err();
+        assert!(
+            err.problems
+                .contains(&"TUBE_API_KEY: 'tube' is not a table".to_string()),
+            "{err}"
+        );
+        assert!(
+            err.problems
+                .contains(&"WEATHER_LAT: 'weather' is not a table".to_string()),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn reads_timezone_and_locale() {
+        let config = load(
+            r#"
+            frame_url = "http://frame"
+            locale = "de_DE"
+            "#,
+            &[("TIMEZONE", "Europe/Berlin")],
+        )
+        .unwrap();
+        assert_eq!(config.localization.tz, chrono_tz::Europe::Berlin);
+        assert_eq!(config.localization.locale, Locale::de_DE);
+
+        let err = load(
+            r#"
+            frame_url = "http://frame"
+            timezone = "Europe/Londres"
+            "#,
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.problems[0].contains("unknown timezone"), "{err}");
+    }
+
+    #[test]
+    fn tfl_sections_share_tube_key() {
+        let config = load(
+            r#"
+            frame_url = "http://frame"
+            [tube]
+            [bikes]
+            stations = [{ id = "BikePoints_1" }]
+            "#,
+            &[("TUBE_API_KEY", "secret")],
+        )
+        .unwrap();
+        assert!(config.sources.is_enabled::<BikesSource>());
+    }
+
+    #[test]
+    fn air_quality_uses_weather_location() {
+        let config = load(
+            r#"
+            frame_url = "http://frame"
+            [weather]
+            [air_quality]
+            "#,
+            &[("WEATHER_LAT", "51.5"), ("WEATHER_LONG", "-0.12")],
+        )
+        .unwrap();
+        assert!(config.sources.is_enabled::<AirQualitySource>());
+    }
+}
@@ -88,6 +88,7 @@ pub struct Inky {
     pub buf: [InkyColour; Self::HEIGHT * Self::WIDTH],
 
     palette: Palette,
+    saturation: f32,
     colour_space: ColourSpace,
 }
 
@@ -185,6 +186,7 @@ impl Inky {
                 &Self::SATURATED_PALETTE_COLOURS,
                 saturation,
             )?,
+            saturation,
             colour_space: ColourSpace::CIELAB,
         })
     }
@@ -361,12 +363,18 @@ impl Inky {
         Ok(())
     }
 
+    #[must_use]
+    pub fn saturation(&self) -> f32 {
+        self.saturation
+    }
+
     pub fn set_saturation(&mut self, saturation: f32) -> Result<(), AppError> {
         self.palette = Palette::from_blend(
             &Self::DESATURATED_PALETTE_COLOURS,
             &Self::SATURATED_PALETTE_COLOURS,
             saturation,
         )?;
+        self.saturation = saturation;
         tracing::info!("Set saturation to {saturation}");
 
         Ok(())
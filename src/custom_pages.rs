@@ -0,0 +1,138 @@
+use crate::{AppError, ServerAppState};
+use axum::debug_handler;
+use axum::extract::{Path, State};
+use axum::response::Html;
+use chrono::Utc;
+use minijinja::Environment;
+use serde::Serialize;
+
+/// Templates starting with `_` are partials, only usable through `include`, `import` or
+/// `extends`.
+fn is_page_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The file of the page called `name` in `dir`, if there is one.
+fn page_file(dir: &std::path::Path, name: &str) -> Option<String> {
+    let file = format!("{name}.html");
+    (is_page_name(name) && dir.join(&file).is_file()).then_some(file)
+}
+
+/// Renders `<name>.html` from `dir`. Templates are re-read each time, which is cheap next to
+/// taking a screenshot of the page.
+fn render(dir: &std::path::Path, name: &str, context: impl Serialize) -> Result<String, AppError> {
+    let file = page_file(dir, name).ok_or(AppError::NotFound)?;
+    let mut env = Environment::new();
+    env.set_loader(minijinja::path_loader(dir));
+    Ok(env.get_template(&file)?.render(context)?)
+}
+
+/// Serves a template from `templates_dir`, so pages can be added or changed without rebuilding
+/// the server. Each enabled data source is in the context under its
+/// own name, along with `now`, `today` and the posted `message`.
+#[debug_handler]
+pub async fn custom_page_handler(
+    State(state): State<ServerAppState>,
+    Path(name): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let Some(dir) = &state.config.templates_dir else {
+        return Err(AppError::NotFound);
+    };
+    // before fetching every source for it
+    if page_file(dir, &name).is_none() {
+        return Err(AppError::NotFound);
+    }
+
+    let sources = &state.config.sources;
+    let data = sources.fetch(&state.client, &sources.names()).await;
+    let localization = &state.config.localization;
+    let mut context = data.to_json();
+    context.insert(
+        "now".to_string(),
+        localization.now().format("%H:%M").to_string().into(),
+    );
+    context.insert(
+        "today".to_string(),
+        localization
+            .format_date(localization.today(), "%A %-d %B")
+            .into(),
+    );
+    context.insert(
+        "message".to_string(),
+        serde_json::to_value(state.message.current(Utc::now())).map_err(anyhow::Error::from)?,
+    );
+
+    let dir = dir.clone();
+    let html = tokio::task::spawn_blocking(move || render(&dir, &name, context))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok(Html(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(test: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "inky_display_templates_{test}_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn renders_templates_with_partials() {
+        let dir = templates(
+            "partials",
+            &[
+                (
+                    "bikes.html",
+                    "{% include '_header.html' %}{% for dock in bikes.value %}{{ dock.name }}: {{ dock.bikes }} {% endfor %}",
+                ),
+                ("_header.html", "<h1>{{ now }}</h1>"),
+            ],
+        );
+        let context = serde_json::json!({
+            "now": "09:30",
+            "bikes": { "value": [{ "name": "Queen St", "bikes": 4 }] },
+        });
+        assert_eq!(
+            render(&dir, "bikes", &context).unwrap(),
+            "<h1>09:30</h1>Queen St: 4 "
+        );
+        assert!(matches!(
+            render(&dir, "_header", &context),
+            Err(AppError::NotFound)
+        ));
+        assert!(matches!(
+            render(&dir, "missing", &context),
+            Err(AppError::NotFound)
+        ));
+        assert!(matches!(
+            render(&dir, "../bikes", &context),
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn reports_where_templates_fail() {
+        let dir = templates(
+            "broken",
+            &[("broken.html", "<p>\n{{ now }}\n{% if now %}\n")],
+        );
+        let Err(AppError::Render(message)) = render(&dir, "broken", serde_json::json!({})) else {
+            panic!("expected a render error");
+        };
+        assert!(message.contains("broken.html line 3"), "{message}");
+    }
+}
@@ -0,0 +1,488 @@
+use crate::data_sources::{
+    AirQualitySource, BikesSource, CalendarSource, ClosuresSource, DataSource, FootballSource,
+    HeadlinesSource, RailSource, StandingsSource, TubeSource, WeatherSource,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Marks a cell of the grid left empty.
+const EMPTY: &str = ".";
+
+/// What a region of the dashboard shows. Each has a partial in `templates/widgets`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Widget {
+    /// Line status, with the weekend's planned closures when the weekend is near.
+    Tube,
+    Rail,
+    Bikes,
+    Agenda,
+    /// Current conditions, the hourly chart and the next days' forecast.
+    Weather,
+    AirQuality,
+    Fixtures,
+    Standings,
+    Headlines,
+}
+
+impl Widget {
+    const ALL: [Widget; 9] = [
+        Widget::Tube,
+        Widget::Rail,
+        Widget::Bikes,
+        Widget::Agenda,
+        Widget::Weather,
+        Widget::AirQuality,
+        Widget::Fixtures,
+        Widget::Standings,
+        Widget::Headlines,
+    ];
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Widget::Tube => "tube",
+            Widget::Rail => "rail",
+            Widget::Bikes => "bikes",
+            Widget::Agenda => "agenda",
+            Widget::Weather => "weather",
+            Widget::AirQuality => "air_quality",
+            Widget::Fixtures => "fixtures",
+            Widget::Standings => "standings",
+            Widget::Headlines => "headlines",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|w| w.name() == name)
+    }
+
+    /// Sources the widget shows.
+    #[must_use]
+    pub fn sources(&self) -> &'static [&'static str] {
+        match self {
+            Widget::Tube => &[TubeSource::NAME, ClosuresSource::NAME],
+            Widget::Rail => &[RailSource::NAME],
+            Widget::Bikes => &[BikesSource::NAME],
+            Widget::Agenda => &[CalendarSource::NAME],
+            Widget::Weather => &[WeatherSource::NAME],
+            Widget::AirQuality => &[AirQualitySource::NAME],
+            Widget::Fixtures => &[FootballSource::NAME],
+            Widget::Standings => &[StandingsSource::NAME],
+            Widget::Headlines => &[HeadlinesSource::NAME],
+        }
+    }
+}
+
+fn default_areas() -> Vec<String> {
+    [
+        "tube weather",
+        "rail weather",
+        "bikes weather",
+        "agenda air_quality",
+        "fixtures standings",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_columns() -> Vec<String> {
+    ["55fr", "45fr"].map(String::from).to_vec()
+}
+
+fn default_rows() -> Vec<String> {
+    ["1fr", "auto", "auto", "auto", "auto"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// Layout of the dashboard as a grid of named regions, in the style of CSS
+/// `grid-template-areas`. Regions whose widget has nothing to show are collapsed, with a
+/// neighbour of the same height or width taking their space.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DashboardConfig {
+    /// One string per row with a region name per column, repeated to span several cells, or
+    /// `.` to leave a cell empty. Each region must be a rectangle.
+    #[serde(default = "default_areas")]
+    pub areas: Vec<String>,
+    /// Width of each column, as a CSS track size like `1fr`, `2fr` or `200px`. Defaults to
+    /// sharing the width evenly.
+    #[serde(default)]
+    pub columns: Vec<String>,
+    /// Height of each row, like `columns`.
+    #[serde(default)]
+    pub rows: Vec<String>,
+    /// Widget shown in each region. Regions named after a widget show that one by default.
+    #[serde(default)]
+    pub regions: HashMap<String, Widget>,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            areas: default_areas(),
+            columns: default_columns(),
+            rows: default_rows(),
+            regions: HashMap::new(),
+        }
+    }
+}
+
+/// A region's cells, as half-open ranges of rows and columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    rows: (usize, usize),
+    columns: (usize, usize),
+}
+
+type Grid = Vec<Vec<String>>;
+
+fn rect_of(grid: &Grid, name: &str) -> Option<Rect> {
+    let mut rect: Option<Rect> = None;
+    for (r, row) in grid.iter().enumerate() {
+        for (c, cell) in row.iter().enumerate() {
+            if cell == name {
+                let rect = rect.get_or_insert(Rect {
+                    rows: (r, r + 1),
+                    columns: (c, c + 1),
+                });
+                rect.rows = (rect.rows.0.min(r), rect.rows.1.max(r + 1));
+                rect.columns = (rect.columns.0.min(c), rect.columns.1.max(c + 1));
+            }
+        }
+    }
+    rect
+}
+
+fn is_region_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// Track sizes go into the page's CSS as they are, so only allow what a size can contain.
+fn is_track_size(size: &str) -> bool {
+    !size.trim().is_empty()
+        && size
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " .,%()-".contains(c))
+}
+
+/// The grid with empty regions collapsed, ready for the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// `grid-template-areas`, `grid-template-columns` and `grid-template-rows`.
+    pub areas: String,
+    pub columns: String,
+    pub rows: String,
+    /// Each region left, in the order they first appear.
+    pub regions: Vec<Region>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub widget: Widget,
+}
+
+impl DashboardConfig {
+    fn grid(&self) -> Grid {
+        self.areas
+            .iter()
+            .map(|row| row.split_whitespace().map(String::from).collect())
+            .collect()
+    }
+
+    fn widget(&self, region: &str) -> Option<Widget> {
+        self.regions
+            .get(region)
+            .copied()
+            .or_else(|| Widget::from_name(region))
+    }
+
+    /// Region names in the order they first appear.
+    fn region_names(grid: &Grid) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for cell in grid.iter().flatten() {
+            if cell != EMPTY && !names.contains(&cell.as_str()) {
+                names.push(cell);
+            }
+        }
+        names
+    }
+
+    /// Track sizes, defaulting to an even share of the space.
+    fn tracks(sizes: &[String], count: usize) -> Vec<String> {
+        if sizes.is_empty() {
+            vec!["1fr".to_string(); count]
+        } else {
+            sizes.to_vec()
+        }
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let grid = self.grid();
+        let width = grid.first().map_or(0, Vec::len);
+        if width == 0 {
+            problems.push("areas: must have at least one row of regions".to_string());
+            return problems;
+        }
+        if grid.iter().any(|row| row.len() != width) {
+            problems.push(format!(
+                "areas: every row needs the same number of columns, {width}"
+            ));
+            return problems;
+        }
+
+        for name in Self::region_names(&grid) {
+            if !is_region_name(name) {
+                problems.push(format!(
+                    "areas: '{name}' isn't a valid region name, use lowercase letters, digits, '_' and '-'"
+                ));
+                continue;
+            }
+            let rect = rect_of(&grid, name).expect("the name is in the grid");
+            let filled = (rect.rows.0..rect.rows.1)
+                .all(|r| (rect.columns.0..rect.columns.1).all(|c| grid[r][c] == name));
+            if !filled {
+                problems.push(format!("areas: region '{name}' isn't a rectangle"));
+            }
+            if self.widget(name).is_none() {
+                problems.push(format!(
+                    "areas: region '{name}' needs a widget, set one in [dashboard.regions]"
+                ));
+            }
+        }
+        for name in self.regions.keys() {
+            if !grid.iter().flatten().any(|cell| cell == name) {
+                problems.push(format!("regions: '{name}' isn't in areas"));
+            }
+        }
+
+        for (key, sizes, count) in [
+            ("columns", &self.columns, width),
+            ("rows", &self.rows, grid.len()),
+        ] {
+            if !sizes.is_empty() && sizes.len() != count {
+                problems.push(format!(
+                    "{key}: expected {count} sizes, got {}",
+                    sizes.len()
+                ));
+            }
+            for size in sizes.iter().filter(|size| !is_track_size(size)) {
+                problems.push(format!("{key}: '{size}' isn't a valid size"));
+            }
+        }
+        problems
+    }
+
+    /// Every source a widget in the layout shows.
+    #[must_use]
+    pub fn sources(&self) -> Vec<&'static str> {
+        let grid = self.grid();
+        let mut sources: Vec<&'static str> = Vec::new();
+        for name in Self::region_names(&grid) {
+            for source in self.widget(name).map_or(&[][..], |w| w.sources()) {
+                if !sources.contains(source) {
+                    sources.push(source);
+                }
+            }
+        }
+        sources
+    }
+
+    /// Lays out the regions whose widget `has_content`. Rows and columns with nothing left in
+    /// them are dropped, then each remaining empty region is taken over by a neighbour spanning
+    /// the same rows (to the left or right) or the same columns (above or below).
+    #[must_use]
+    pub fn layout(&self, has_content: impl Fn(Widget) -> bool) -> Layout {
+        let mut grid = self.grid();
+        let mut rows = Self::tracks(&self.rows, grid.len());
+        let mut columns = Self::tracks(&self.columns, grid.first().map_or(0, Vec::len));
+
+        let empty: Vec<String> = Self::region_names(&grid)
+            .into_iter()
+            .filter(|name| !self.widget(name).is_some_and(&has_content))
+            .map(String::from)
+            .collect();
+        let is_empty = |cell: &String| empty.contains(cell);
+        // only tracks that lost a region, so blank ones in the config are kept as spacing
+        let droppable = |cells: &[&String]| {
+            cells.iter().any(|cell| is_empty(cell))
+                && cells.iter().all(|cell| is_empty(cell) || *cell == EMPTY)
+        };
+
+        for r in (0..grid.len()).rev() {
+            if droppable(&grid[r].iter().collect::<Vec<_>>()) {
+                grid.remove(r);
+                rows.remove(r);
+            }
+        }
+        for c in (0..columns.len()).rev() {
+            if droppable(&grid.iter().map(|row| &row[c]).collect::<Vec<_>>()) {
+                for row in &mut grid {
+                    row.remove(c);
+                }
+                columns.remove(c);
+            }
+        }
+
+        // taking over one region can line a neighbour up with another, so go until nothing moves
+        let mut pending: Vec<&String> = empty.iter().collect();
+        while let Some(i) = (0..pending.len()).find(|&i| expand_into(&mut grid, pending[i], &empty))
+        {
+            pending.remove(i);
+        }
+        for cell in grid.iter_mut().flatten().filter(|cell| is_empty(cell)) {
+            *cell = EMPTY.to_string();
+        }
+
+        Layout {
+            areas: grid
+                .iter()
+                .map(|row| format!("\"{}\"", row.join(" ")))
+                .collect::<Vec<_>>()
+                .join(" "),
+            columns: columns.join(" "),
+            rows: rows.join(" "),
+            regions: Self::region_names(&grid)
+                .into_iter()
+                .map(|name| Region {
+                    name: name.to_string(),
+                    widget: self
+                        .widget(name)
+                        .expect("checked when the config was loaded"),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Has a neighbour of the empty region take its cells over, returning whether one could.
+/// Neighbours that are empty themselves, or blank cells, can't.
+fn expand_into(grid: &mut Grid, region: &str, empty: &[String]) -> bool {
+    // gone with a dropped row or column
+    let Some(Rect { rows, columns }) = rect_of(grid, region) else {
+        return true;
+    };
+    // cells just outside each side of the region
+    let left = columns.0.checked_sub(1).map(|c| (rows.0, c));
+    let right = (columns.1 < grid[0].len()).then_some((rows.0, columns.1));
+    let above = rows.0.checked_sub(1).map(|r| (r, columns.0));
+    let below = (rows.1 < grid.len()).then_some((rows.1, columns.0));
+
+    let candidates = [(left, true), (right, true), (above, false), (below, false)];
+    for ((r, c), beside) in candidates
+        .into_iter()
+        .filter_map(|(cell, beside)| Some((cell?, beside)))
+    {
+        let name = grid[r][c].clone();
+        if name == EMPTY || empty.contains(&name) {
+            continue;
+        }
+        let neighbour = rect_of(grid, &name).expect("the name is in the grid");
+        let lines_up = if beside {
+            neighbour.rows == rows
+        } else {
+            neighbour.columns == columns
+        };
+        if lines_up {
+            for row in &mut grid[rows.0..rows.1] {
+                for cell in &mut row[columns.0..columns.1] {
+                    cell.clone_from(&name);
+                }
+            }
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> DashboardConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn collapses_regions_without_content() {
+        let config = DashboardConfig::default();
+        assert!(config.validate().is_empty());
+
+        let full = config.layout(|_| true);
+        assert_eq!(full.regions.len(), 8);
+        assert_eq!(full.columns, "55fr 45fr");
+
+        // the tube takes over the empty rows below it, and standings the fixtures' space
+        let layout =
+            config.layout(|w| !matches!(w, Widget::Rail | Widget::Bikes | Widget::Fixtures));
+        assert_eq!(
+            layout.areas,
+            r#""tube weather" "tube weather" "tube weather" "agenda air_quality" "standings standings""#
+        );
+        assert_eq!(layout.rows, "1fr auto auto auto auto");
+
+        // without football the bottom row goes, and without weather the column
+        let layout = config.layout(|w| {
+            !matches!(
+                w,
+                Widget::Weather | Widget::AirQuality | Widget::Fixtures | Widget::Standings
+            )
+        });
+        assert_eq!(layout.areas, r#""tube" "rail" "bikes" "agenda""#);
+        assert_eq!(layout.columns, "55fr");
+        assert_eq!(layout.rows, "1fr auto auto auto");
+    }
+
+    #[test]
+    fn binds_regions_to_widgets() {
+        let dashboard = config(
+            r#"
+            areas = ["left right", "left right", "bottom bottom"]
+            rows = ["auto", "1fr", "120px"]
+            [regions]
+            left = "agenda"
+            right = "headlines"
+            bottom = "fixtures"
+            "#,
+        );
+        assert!(dashboard.validate().is_empty());
+        assert_eq!(
+            dashboard.sources(),
+            [
+                CalendarSource::NAME,
+                HeadlinesSource::NAME,
+                FootballSource::NAME
+            ]
+        );
+        let layout = dashboard.layout(|_| true);
+        assert_eq!(layout.columns, "1fr 1fr");
+        assert_eq!(layout.regions[1].widget, Widget::Headlines);
+
+        let problems = config(
+            r#"
+            areas = ["a b", "b a", "c"]
+            "#,
+        )
+        .validate();
+        assert_eq!(problems.len(), 1, "{problems:?}");
+
+        // not a rectangle, too many columns, CSS in a size and a region that isn't in the grid
+        let problems = config(
+            r#"
+            areas = ["tube rail", "bikes rail", "tube tube"]
+            columns = ["1fr; color: red", "1fr", "1fr"]
+            [regions]
+            nowhere = "tube"
+            "#,
+        )
+        .validate();
+        assert_eq!(problems.len(), 4, "{problems:?}");
+        assert!(toml::from_str::<DashboardConfig>("regions = { rail = \"trains\" }").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use super::DataSource;
+use crate::time::Localization;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_base_url() -> String {
+    "https://air-quality-api.open-meteo.com".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AirQualityConfig {
+    /// Defaults to the location in `[weather]`.
+    pub lat: f32,
+    pub long: f32,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+}
+
+/// How bad a reading is, from 0 (fine) to 3 (very high), for picking its colour.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Band {
+    #[default]
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
+}
+
+impl Band {
+    /// Bands a value by the upper bounds of `Low`, `Moderate` and `High`.
+    fn from_thresholds(value: f32, [low, moderate, high]: [f32; 3]) -> Self {
+        if value < low {
+            Self::Low
+        } else if value < moderate {
+            Self::Moderate
+        } else if value < high {
+            Self::High
+        } else {
+            Self::VeryHigh
+        }
+    }
+
+    #[must_use]
+    pub fn level(self) -> u8 {
+        self as u8
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Band::Low => "Low",
+            Band::Moderate => "Moderate",
+            Band::High => "High",
+            Band::VeryHigh => "Very high",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Reading {
+    pub name: &'static str,
+    pub value: i32,
+    pub band: Band,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AirQuality {
+    /// European AQI, 0-100+.
+    pub aqi: Reading,
+    /// PM2.5, NO2 and ozone in μg/m³.
+    pub pollutants: Vec<Reading>,
+    /// Grass, tree and weed pollen in grains/m³. Empty outside Europe or out of season.
+    pub pollen: Vec<Reading>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoAirQuality {
+    current: OpenMeteoAirCurrent,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoAirCurrent {
+    european_aqi: Option<f32>,
+    pm2_5: Option<f32>,
+    nitrogen_dioxide: Option<f32>,
+    ozone: Option<f32>,
+    grass_pollen: Option<f32>,
+    alder_pollen: Option<f32>,
+    birch_pollen: Option<f32>,
+    olive_pollen: Option<f32>,
+    mugwort_pollen: Option<f32>,
+    ragweed_pollen: Option<f32>,
+}
+
+/// Air quality and pollen from Open-Meteo.
+pub struct AirQualitySource {
+    config: AirQualityConfig,
+    localization: Localization,
+}
+
+impl DataSource for AirQualitySource {
+    const NAME: &'static str = "air_quality";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    type Config = AirQualityConfig;
+    type Output = AirQuality;
+
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        if !(-90.0..=90.0).contains(&config.lat) {
+            anyhow::bail!("lat {} is out of range", config.lat);
+        }
+        if !(-180.0..=180.0).contains(&config.long) {
+            anyhow::bail!("long {} is out of range", config.long);
+        }
+        Url::parse(&config.base_url)
+            .map_err(|e| anyhow::anyhow!("base_url '{}' {e}", config.base_url))?;
+        Ok(Self {
+            config,
+            localization: *localization,
+        })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let url = Url::parse_with_params(
+            &format!(
+                "{}/v1/air-quality",
+                self.config.base_url.trim_end_matches('/')
+            ),
+            [
+                ("latitude", self.config.lat.to_string().as_str()),
+                ("longitude", self.config.long.to_string().as_str()),
+                (
+                    "current",
+                    "european_aqi,pm2_5,nitrogen_dioxide,ozone,grass_pollen,alder_pollen,\
+                     birch_pollen,olive_pollen,mugwort_pollen,ragweed_pollen",
+                ),
+                ("timezone", self.localization.tz.name()),
+            ],
+        )?;
+        let response: OpenMeteoAirQuality = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        to_air_quality(&response.current)
+    }
+}
+
+fn to_air_quality(current: &OpenMeteoAirCurrent) -> anyhow::Result<AirQuality> {
+    let reading = |name, value: f32, thresholds| Reading {
+        name,
+        value: value.round() as i32,
+        band: Band::from_thresholds(value, thresholds),
+    };
+
+    let aqi = current
+        .european_aqi
+        .ok_or_else(|| anyhow::anyhow!("No air quality index for this location"))?;
+
+    // bands follow the European AQI ones for each pollutant
+    let pollutants = [
+        ("PM2.5", current.pm2_5, [10.0, 25.0, 50.0]),
+        ("NO2", current.nitrogen_dioxide, [40.0, 120.0, 230.0]),
+        ("O3", current.ozone, [100.0, 130.0, 240.0]),
+    ]
+    .into_iter()
+    .filter_map(|(name, value, thresholds)| value.map(|v| reading(name, v, thresholds)))
+    .collect();
+
+    let max = |values: &[Option<f32>]| values.iter().flatten().copied().reduce(f32::max);
+    let pollen = [
+        ("Grass", current.grass_pollen, [10.0, 50.0, 150.0]),
+        (
+            "Tree",
+            max(&[
+                current.alder_pollen,
+                current.birch_pollen,
+                current.olive_pollen,
+            ]),
+            [15.0, 90.0, 1500.0],
+        ),
+        (
+            "Weed",
+            max(&[current.mugwort_pollen, current.ragweed_pollen]),
+            [10.0, 50.0, 500.0],
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, value, thresholds)| value.map(|v| reading(name, v, thresholds)))
+    .collect();
+
+    Ok(AirQuality {
+        aqi: reading("AQI", aqi, [40.0, 60.0, 80.0]),
+        pollutants,
+        pollen,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::fixture_server;
+    use axum::Json;
+    use axum::extract::Query;
+    use axum::routing::get;
+    use std::collections::HashMap;
+
+    /// Serves a recorded response, so the source can be tested without Open-Meteo.
+    async fn stand_in() -> String {
+        async fn air_quality(
+            Query(params): Query<HashMap<String, String>>,
+        ) -> Json<serde_json::Value> {
+            assert_eq!(params["latitude"], "51.5");
+            assert_eq!(params["timezone"], "Europe/London");
+            Json(serde_json::json!({
+                "latitude": 51.5, "longitude": -0.12,
+                "current": {
+                    "time": "2025-06-01T14:00",
+                    "european_aqi": 47, "pm2_5": 8.2, "nitrogen_dioxide": 21.5, "ozone": 131.0,
+                    "grass_pollen": 64.3, "alder_pollen": 0.0, "birch_pollen": 3.1,
+                    "olive_pollen": null, "mugwort_pollen": null, "ragweed_pollen": null
+                }
+            }))
+        }
+
+        let app = axum::Router::new().route("/v1/air-quality", get(air_quality));
+        fixture_server(app).await
+    }
+
+    #[tokio::test]
+    async fn bands_readings() {
+        let config = AirQualityConfig {
+            lat: 51.5,
+            long: -0.12,
+            base_url: stand_in().await,
+        };
+        let source = AirQualitySource::new(config, &Localization::default()).unwrap();
+        let air = source.fetch(&Client::new()).await.unwrap();
+
+        assert_eq!((air.aqi.value, air.aqi.band), (47, Band::Moderate));
+        let bands = |readings: &[Reading]| -> Vec<_> {
+            readings.iter().map(|r| (r.name, r.value, r.band)).collect()
+        };
+        assert_eq!(
+            bands(&air.pollutants),
+            [
+                ("PM2.5", 8, Band::Low),
+                ("NO2", 22, Band::Low),
+                ("O3", 131, Band::High)
+            ]
+        );
+        assert_eq!(
+            bands(&air.pollen),
+            [("Grass", 64, Band::High), ("Tree", 3, Band::Low),]
+        );
+    }
+}
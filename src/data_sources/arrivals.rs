@@ -0,0 +1,246 @@
+use super::DataSource;
+use super::tube::tfl_get;
+use crate::time::Localization;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_count() -> usize {
+    6
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArrivalsConfig {
+    pub api_key: String,
+    pub stops: Vec<StopConfig>,
+    /// How many departures to show for each stop.
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StopConfig {
+    /// TfL StopPoint id, e.g. `940GZZLUOXC` for Oxford Circus or `490008660N` for a bus stop.
+    pub id: String,
+    /// Name to show, defaults to the station name from TfL.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Only show departures from platforms whose name contains one of these, e.g. `Westbound`
+    /// or `Platform 2`.
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Only show departures going `inbound` or `outbound`.
+    #[serde(default)]
+    pub direction: Option<String>,
+    /// Only show these lines or bus routes.
+    #[serde(default)]
+    pub lines: Vec<String>,
+}
+
+impl StopConfig {
+    fn wants(&self, prediction: &TflPrediction) -> bool {
+        let platform = prediction.platform_name.to_lowercase();
+        (self.platforms.is_empty()
+            || self
+                .platforms
+                .iter()
+                .any(|p| platform.contains(&p.to_lowercase())))
+            && self
+                .direction
+                .as_ref()
+                .is_none_or(|d| d.eq_ignore_ascii_case(&prediction.direction))
+            && (self.lines.is_empty()
+                || self
+                    .lines
+                    .iter()
+                    .any(|l| l.eq_ignore_ascii_case(&prediction.line_name)))
+    }
+}
+
+/// The next departures from one stop.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StopDepartures {
+    pub id: String,
+    pub name: String,
+    pub departures: Vec<Departure>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Departure {
+    pub line: String,
+    pub destination: String,
+    pub platform: String,
+    /// Whole minutes until it arrives at the stop.
+    pub minutes: u32,
+    /// Expected arrival time, e.g. `14:05`.
+    pub expected: String,
+}
+
+impl Departure {
+    /// `Due` or e.g. `4 min`.
+    #[must_use]
+    pub fn due_label(&self) -> String {
+        if self.minutes == 0 {
+            "Due".to_string()
+        } else {
+            format!("{} min", self.minutes)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TflPrediction {
+    station_name: String,
+    line_name: String,
+    #[serde(default)]
+    platform_name: String,
+    #[serde(default)]
+    direction: String,
+    #[serde(default)]
+    destination_name: Option<String>,
+    #[serde(default)]
+    towards: Option<String>,
+    /// Seconds until it arrives.
+    time_to_station: i64,
+    expected_arrival: DateTime<Utc>,
+}
+
+/// Live departures from the configured TfL stations and bus stops.
+pub struct ArrivalsSource {
+    config: ArrivalsConfig,
+    localization: Localization,
+}
+
+impl DataSource for ArrivalsSource {
+    const NAME: &'static str = "arrivals";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+    type Config = ArrivalsConfig;
+    type Output = Vec<StopDepartures>;
+
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        if config.api_key.trim().is_empty() {
+            anyhow::bail!("api_key must not be empty");
+        }
+        if config.stops.is_empty() {
+            anyhow::bail!("at least one stop must be configured");
+        }
+        if config.count == 0 {
+            anyhow::bail!("count must be at least 1");
+        }
+        if let Some(direction) = config
+            .stops
+            .iter()
+            .filter_map(|s| s.direction.as_deref())
+            .find(|d| !matches!(*d, "inbound" | "outbound"))
+        {
+            anyhow::bail!("direction must be 'inbound' or 'outbound', got '{direction}'");
+        }
+        Ok(Self {
+            config,
+            localization: *localization,
+        })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let mut stops = Vec::new();
+        for stop in &self.config.stops {
+            let predictions: Vec<TflPrediction> = tfl_get(
+                client,
+                &self.config.api_key,
+                &format!("StopPoint/{}/Arrivals", stop.id),
+            )
+            .await?;
+            stops.push(select_departures(
+                stop,
+                predictions,
+                self.config.count,
+                &self.localization,
+            ));
+        }
+        Ok(stops)
+    }
+}
+
+/// Keeps the soonest `count` departures matching the stop's filters.
+fn select_departures(
+    stop: &StopConfig,
+    mut predictions: Vec<TflPrediction>,
+    count: usize,
+    localization: &Localization,
+) -> StopDepartures {
+    predictions.sort_by_key(|p| p.time_to_station);
+    let name = stop
+        .name
+        .clone()
+        .or_else(|| predictions.first().map(|p| p.station_name.clone()))
+        .unwrap_or_else(|| stop.id.clone());
+
+    let departures = predictions
+        .into_iter()
+        .filter(|p| stop.wants(p))
+        .take(count)
+        .map(|p| Departure {
+            destination: p
+                .destination_name
+                .or(p.towards)
+                .unwrap_or_else(|| "Check front of train".to_string()),
+            line: p.line_name,
+            platform: p.platform_name,
+            minutes: u32::try_from(p.time_to_station / 60).unwrap_or(0),
+            expected: localization.format(&p.expected_arrival, "%H:%M"),
+        })
+        .collect();
+
+    StopDepartures {
+        id: stop.id.clone(),
+        name,
+        departures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prediction(line: &str, platform: &str, direction: &str, secs: i64) -> TflPrediction {
+        serde_json::from_value(serde_json::json!({
+            "stationName": "Oxford Circus Underground Station",
+            "lineName": line,
+            "platformName": platform,
+            "direction": direction,
+            "destinationName": "Brixton Underground Station",
+            "timeToStation": secs,
+            "expectedArrival": "2025-06-01T13:05:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn filters_and_orders_departures() {
+        let stop: StopConfig = toml::from_str(
+            r#"
+            id = "940GZZLUOXC"
+            platforms = ["southbound"]
+            direction = "outbound"
+            "#,
+        )
+        .unwrap();
+        let predictions = vec![
+            prediction("Victoria", "Southbound - Platform 4", "outbound", 400),
+            prediction("Victoria", "Northbound - Platform 3", "inbound", 30),
+            prediction("Victoria", "Southbound - Platform 4", "outbound", 45),
+            prediction("Victoria", "Southbound - Platform 4", "outbound", 700),
+        ];
+
+        let stop = select_departures(&stop, predictions, 2, &Localization::default());
+        assert_eq!(stop.name, "Oxford Circus Underground Station");
+        let due: Vec<_> = stop.departures.iter().map(Departure::due_label).collect();
+        assert_eq!(due, ["Due", "6 min"]);
+        assert_eq!(stop.departures[0].expected, "14:05");
+    }
+}
@@ -0,0 +1,167 @@
+use super::DataSource;
+use super::tube::tfl_get;
+use crate::time::Localization;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_threshold() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BikesConfig {
+    pub api_key: String,
+    pub stations: Vec<DockConfig>,
+    /// Bike and e-bike counts below this are highlighted.
+    #[serde(default = "default_threshold")]
+    pub min_bikes: u32,
+    /// Free dock counts below this are highlighted.
+    #[serde(default = "default_threshold")]
+    pub min_docks: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DockConfig {
+    /// TfL BikePoint id, e.g. `BikePoints_1`.
+    pub id: String,
+    /// Name to show, defaults to the name from TfL, which is usually long.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Availability at one docking station.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DockStatus {
+    pub name: String,
+    pub bikes: u32,
+    pub ebikes: u32,
+    pub empty_docks: u32,
+    pub bikes_low: bool,
+    pub ebikes_low: bool,
+    pub docks_low: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TflBikePoint {
+    common_name: String,
+    additional_properties: Vec<TflProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TflProperty {
+    key: String,
+    value: String,
+}
+
+impl TflBikePoint {
+    fn count(&self, key: &str) -> anyhow::Result<u32> {
+        let property = self
+            .additional_properties
+            .iter()
+            .find(|p| p.key == key)
+            .ok_or_else(|| anyhow::anyhow!("{} has no {key}", self.common_name))?;
+        Ok(property.value.parse()?)
+    }
+}
+
+/// Santander Cycles availability at the configured docking stations.
+pub struct BikesSource {
+    config: BikesConfig,
+}
+
+impl DataSource for BikesSource {
+    const NAME: &'static str = "bikes";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+    type Config = BikesConfig;
+    type Output = Vec<DockStatus>;
+
+    fn new(config: Self::Config, _localization: &Localization) -> anyhow::Result<Self> {
+        if config.api_key.trim().is_empty() {
+            anyhow::bail!("api_key must not be empty");
+        }
+        if config.stations.is_empty() {
+            anyhow::bail!("at least one station must be configured");
+        }
+        Ok(Self { config })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let mut docks = Vec::new();
+        for station in &self.config.stations {
+            let point: TflBikePoint = tfl_get(
+                client,
+                &self.config.api_key,
+                &format!("BikePoint/{}", station.id),
+            )
+            .await?;
+            docks.push(to_dock_status(&self.config, station, &point)?);
+        }
+        Ok(docks)
+    }
+}
+
+fn to_dock_status(
+    config: &BikesConfig,
+    station: &DockConfig,
+    point: &TflBikePoint,
+) -> anyhow::Result<DockStatus> {
+    // NbBikes counts e-bikes too, so use the standard bike count on its own
+    let bikes = point.count("NbStandardBikes")?;
+    let ebikes = point.count("NbEBikes")?;
+    let empty_docks = point.count("NbEmptyDocks")?;
+
+    Ok(DockStatus {
+        name: station
+            .name
+            .clone()
+            .unwrap_or_else(|| point.common_name.clone()),
+        bikes,
+        ebikes,
+        empty_docks,
+        bikes_low: bikes < config.min_bikes,
+        ebikes_low: ebikes < config.min_bikes,
+        docks_low: empty_docks < config.min_docks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_counts_and_thresholds() {
+        let config: BikesConfig = toml::from_str(
+            r#"
+            api_key = "key"
+            stations = [{ id = "BikePoints_1" }]
+            min_bikes = 2
+            "#,
+        )
+        .unwrap();
+        let point: TflBikePoint = serde_json::from_value(serde_json::json!({
+            "id": "BikePoints_1",
+            "commonName": "River Street , Clerkenwell",
+            "additionalProperties": [
+                { "key": "NbBikes", "value": "6" },
+                { "key": "NbEmptyDocks", "value": "12" },
+                { "key": "NbDocks", "value": "19" },
+                { "key": "NbStandardBikes", "value": "5" },
+                { "key": "NbEBikes", "value": "1" }
+            ]
+        }))
+        .unwrap();
+
+        let dock = to_dock_status(&config, &config.stations[0], &point).unwrap();
+        assert_eq!(dock.name, "River Street , Clerkenwell");
+        assert_eq!((dock.bikes, dock.ebikes, dock.empty_docks), (5, 1, 12));
+        assert_eq!(
+            (dock.bikes_low, dock.ebikes_low, dock.docks_low),
+            (false, true, false)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use super::{AnyOutput, ErasedSource};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use reqwest::Client;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Minimum time between attempts while a source is failing, so every render doesn't retry.
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// A fetched value along with how fresh it is.
+#[derive(Debug, Clone)]
+pub struct Fetched<T> {
+    pub value: T,
+    pub fetched_at: DateTime<Utc>,
+    /// Set when refreshing has been failing since this time, and `value` is the last good one.
+    pub stale_since: Option<DateTime<Utc>>,
+    /// Timezone to show the times in.
+    tz: Tz,
+}
+
+impl<T> Fetched<T> {
+    #[must_use]
+    pub fn age(&self) -> TimeDelta {
+        Utc::now() - self.fetched_at
+    }
+
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.stale_since.is_some()
+    }
+
+    /// A short marker for templates, e.g. "stale since 10:42".
+    #[must_use]
+    pub fn stale_label(&self) -> Option<String> {
+        self.stale_since.map(|_| {
+            format!(
+                "stale since {}",
+                self.fetched_at.with_timezone(&self.tz).format("%H:%M")
+            )
+        })
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Fetched<U> {
+        Fetched {
+            value: f(self.value),
+            fetched_at: self.fetched_at,
+            stale_since: self.stale_since,
+            tz: self.tz,
+        }
+    }
+
+    pub fn as_ref(&self) -> Fetched<&T> {
+        Fetched {
+            value: &self.value,
+            fetched_at: self.fetched_at,
+            stale_since: self.stale_since,
+            tz: self.tz,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheState {
+    value: Option<Fetched<AnyOutput>>,
+    last_error: Option<String>,
+    last_attempt: Option<Instant>,
+    /// Set while a fetch is in flight, so concurrent readers don't start another one.
+    refreshing: bool,
+}
+
+impl CacheState {
+    fn result(&self) -> Result<Fetched<AnyOutput>, String> {
+        match (&self.value, &self.last_error) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(e)) => Err(e.clone()),
+            (None, None) => Err("No data yet".to_string()),
+        }
+    }
+}
+
+/// Holds the last good value of a source and refreshes it when it gets older than the TTL.
+pub(super) struct SourceCache {
+    name: &'static str,
+    source: Arc<dyn ErasedSource>,
+    ttl: Duration,
+    tz: Tz,
+    state: Mutex<CacheState>,
+    refreshed: Notify,
+}
+
+/// Clears the in-flight flag and wakes waiting readers, even if the fetch is cancelled.
+struct RefreshGuard<'a>(&'a SourceCache);
+
+impl Drop for RefreshGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock().refreshing = false;
+        self.0.refreshed.notify_waiters();
+    }
+}
+
+impl SourceCache {
+    pub(super) fn new(
+        name: &'static str,
+        source: Arc<dyn ErasedSource>,
+        ttl: Duration,
+        tz: Tz,
+    ) -> Self {
+        Self {
+            name,
+            source,
+            ttl,
+            tz,
+            state: Mutex::new(CacheState::default()),
+            refreshed: Notify::new(),
+        }
+    }
+
+    pub(super) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub(super) fn to_json(&self, value: &AnyOutput) -> serde_json::Value {
+        self.source.to_json(value)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().expect("mutex poisoned")
+    }
+
+    /// Fetches without holding the lock, so a slow API doesn't block readers of the current
+    /// value. The caller must have set `refreshing`.
+    async fn fetch_and_store(&self, client: &Client) {
+        let _guard = RefreshGuard(self);
+        let result = self.source.fetch(client).await;
+
+        let mut state = self.lock();
+        match result {
+            Ok(value) => {
+                state.value = Some(Fetched {
+                    value,
+                    fetched_at: Utc::now(),
+                    stale_since: None,
+                    tz: self.tz,
+                });
+                state.last_error = None;
+            }
+            Err(e) => {
+                tracing::error!("Failed to fetch {}: {e:?}", self.name);
+                if let Some(value) = &mut state.value {
+                    value.stale_since.get_or_insert_with(Utc::now);
+                }
+                state.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Marks a fetch as started, unless one already is.
+    fn start_refresh(state: &mut CacheState) -> bool {
+        if state.refreshing {
+            return false;
+        }
+        state.refreshing = true;
+        state.last_attempt = Some(Instant::now());
+        true
+    }
+
+    /// Fetches a new value regardless of how fresh the current one is, unless a fetch is already
+    /// in flight.
+    pub(super) async fn refresh(&self, client: &Client) {
+        if Self::start_refresh(&mut self.lock()) {
+            self.fetch_and_store(client).await;
+        }
+    }
+
+    /// Returns the cached value, fetching it first if it has expired. Falls back to the last
+    /// good value when that fetch fails. While another fetch is in flight this returns the
+    /// current value, or waits for that fetch when there is none yet.
+    pub(super) async fn get(&self, client: &Client) -> Result<Fetched<AnyOutput>, String> {
+        loop {
+            let refreshed = self.refreshed.notified();
+            let fetch = {
+                let mut state = self.lock();
+                if state.refreshing {
+                    if state.value.is_some() {
+                        return state.result();
+                    }
+                    false
+                } else {
+                    let expired = state
+                        .value
+                        .as_ref()
+                        .is_none_or(|v| v.age().to_std().unwrap_or_default() >= self.ttl);
+                    let may_retry = state
+                        .last_attempt
+                        .is_none_or(|t| state.last_error.is_none() || t.elapsed() >= RETRY_AFTER);
+                    if !(expired && may_retry) {
+                        return state.result();
+                    }
+                    Self::start_refresh(&mut state)
+                }
+            };
+
+            if fetch {
+                self.fetch_and_store(client).await;
+                return self.lock().result();
+            }
+            refreshed.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::DataSource;
+    use crate::time::Localization;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Succeeds on the first fetch and fails on every one after.
+    struct Flaky(AtomicUsize);
+
+    impl DataSource for Flaky {
+        const NAME: &'static str = "flaky";
+        const REFRESH_INTERVAL: Duration = Duration::ZERO;
+
+        type Config = ();
+        type Output = usize;
+
+        fn new((): Self::Config, _: &Localization) -> anyhow::Result<Self> {
+            Ok(Self(AtomicUsize::new(0)))
+        }
+
+        async fn fetch(&self, _client: &Client) -> anyhow::Result<Self::Output> {
+            match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => Ok(42),
+                _ => anyhow::bail!("upstream is down"),
+            }
+        }
+    }
+
+    /// Counts its fetches, taking a second over each one after the first.
+    struct Slow(AtomicUsize);
+
+    impl DataSource for Slow {
+        const NAME: &'static str = "slow";
+        const REFRESH_INTERVAL: Duration = Duration::ZERO;
+
+        type Config = ();
+        type Output = usize;
+
+        fn new((): Self::Config, _: &Localization) -> anyhow::Result<Self> {
+            Ok(Self(AtomicUsize::new(0)))
+        }
+
+        async fn fetch(&self, _client: &Client) -> anyhow::Result<Self::Output> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            if n > 0 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(n)
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_last_good_value_when_fetch_fails() {
+        let client = Client::new();
+        let cache = SourceCache::new(
+            "flaky",
+            Arc::new(Flaky::new((), &Localization::default()).unwrap()),
+            Duration::ZERO,
+            chrono_tz::UTC,
+        );
+
+        let first = cache.get(&client).await.unwrap();
+        assert!(!first.is_stale());
+
+        let second = cache.get(&client).await.unwrap();
+        assert!(second.is_stale());
+        assert_eq!(second.fetched_at, first.fetched_at);
+        assert_eq!(second.value.downcast_ref::<usize>(), Some(&42));
+        assert!(second.stale_label().is_some());
+    }
+
+    #[tokio::test]
+    async fn reports_error_without_a_good_value() {
+        let client = Client::new();
+        let source = Flaky::new((), &Localization::default()).unwrap();
+        source.0.store(1, Ordering::SeqCst);
+        let cache = SourceCache::new("flaky", Arc::new(source), Duration::ZERO, chrono_tz::UTC);
+
+        assert_eq!(cache.get(&client).await.unwrap_err(), "upstream is down");
+    }
+
+    #[tokio::test]
+    async fn serves_current_value_while_refreshing() {
+        let client = Client::new();
+        let ttl = Duration::from_secs(3600);
+        let cache = SourceCache::new(
+            "slow",
+            Arc::new(Slow::new((), &Localization::default()).unwrap()),
+            ttl,
+            chrono_tz::UTC,
+        );
+        cache.get(&client).await.unwrap();
+
+        let read = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            tokio::time::timeout(Duration::from_millis(100), cache.get(&client)).await
+        };
+        let ((), read) = tokio::join!(cache.refresh(&client), read);
+
+        let value = read.expect("get waited for the slow fetch").unwrap();
+        assert_eq!(value.value.downcast_ref::<usize>(), Some(&0));
+        assert_eq!(
+            cache
+                .get(&client)
+                .await
+                .unwrap()
+                .value
+                .downcast_ref::<usize>(),
+            Some(&1)
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Just enough of iCalendar (RFC 5545) for an agenda: events, their times and how they recur.
+
+use super::recurrence::Rule;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// A time as written in the calendar, in local time of its timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Time {
+    pub local: NaiveDateTime,
+    /// `None` for dates, i.e. all-day events.
+    pub tz: Option<Tz>,
+}
+
+impl Time {
+    #[must_use]
+    pub fn is_date(&self) -> bool {
+        self.tz.is_none()
+    }
+
+    /// The same instant in local time of `tz`, or as is for dates.
+    #[must_use]
+    pub fn in_tz(&self, tz: Option<Tz>) -> NaiveDateTime {
+        match (self.tz, tz) {
+            (Some(from), Some(to)) if from != to => {
+                to_utc(self.local, from).with_timezone(&to).naive_local()
+            }
+            _ => self.local,
+        }
+    }
+}
+
+/// Localises a wall clock time, moving times in a DST gap forward an hour.
+pub(super) fn to_utc(local: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(local + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .map_or_else(|| local.and_utc(), |t| t.with_timezone(&Utc))
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub location: Option<String>,
+    pub start: Time,
+    pub duration: TimeDelta,
+    pub rule: Option<Rule>,
+    /// Starts of cancelled occurrences, in the timezone of `start`.
+    pub exdates: Vec<NaiveDateTime>,
+    /// Set when this event replaces one occurrence of a recurring event with the same uid.
+    pub recurrence_id: Option<NaiveDateTime>,
+    pub cancelled: bool,
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Joins folded lines back up, a line starting with a space or tab continuing the previous one.
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits `NAME;PARAM=value;PARAM="quoted":VALUE`.
+fn parse_property(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                parts.push(&line[part_start..i]);
+                part_start = i + 1;
+            }
+            ':' if !in_quotes => {
+                parts.push(&line[part_start..i]);
+                let (name, params) = parts.split_first()?;
+                return Some(Property {
+                    name: name.to_ascii_uppercase(),
+                    params: params
+                        .iter()
+                        .filter_map(|p| p.split_once('='))
+                        .map(|(k, v)| (k.to_string(), v.trim_matches('"').to_string()))
+                        .collect(),
+                    value: line[i + 1..].to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Parses a `DATE` or `DATE-TIME` value. Floating times and timezones that aren't IANA names,
+/// e.g. Outlook's `GMT Standard Time`, are taken to be in `default_tz`.
+pub(super) fn parse_time(value: &str, tzid: Option<&str>, default_tz: Tz) -> anyhow::Result<Time> {
+    let value = value.trim();
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d")?;
+        return Ok(Time {
+            local: date.and_time(chrono::NaiveTime::MIN),
+            tz: None,
+        });
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return Ok(Time {
+            local: NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")?,
+            tz: Some(Tz::UTC),
+        });
+    }
+    let tz = tzid
+        .and_then(|id| id.trim_start_matches('/').parse().ok())
+        .unwrap_or(default_tz);
+    Ok(Time {
+        local: NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")?,
+        tz: Some(tz),
+    })
+}
+
+/// Parses a duration like `PT1H30M`, `P1D` or `-P1W`.
+pub(super) fn parse_duration(value: &str) -> anyhow::Result<TimeDelta> {
+    let invalid = || anyhow::anyhow!("invalid duration '{value}'");
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let part = match unit {
+                    'W' => TimeDelta::try_weeks(n),
+                    'D' => TimeDelta::try_days(n),
+                    'H' => TimeDelta::try_hours(n),
+                    'M' => TimeDelta::try_minutes(n),
+                    'S' => TimeDelta::try_seconds(n),
+                    _ => None,
+                };
+                total = part
+                    .and_then(|part| total.checked_add(&part))
+                    .ok_or_else(invalid)?;
+            }
+        }
+    }
+    Ok(total * sign)
+}
+
+/// Every event in the calendar, recurring ones unexpanded. Events that can't be read are
+/// skipped.
+pub(super) fn parse_events(ics: &str, default_tz: Tz) -> anyhow::Result<Vec<Event>> {
+    let lines = unfold(ics);
+    if !lines
+        .first()
+        .is_some_and(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        anyhow::bail!("not an iCalendar file");
+    }
+
+    let mut events = Vec::new();
+    let mut properties: Option<Vec<Property>> = None;
+    // alarms and the like inside an event
+    let mut nested = 0;
+    for line in &lines {
+        let Some(property) = parse_property(line) else {
+            continue;
+        };
+        match (
+            property.name.as_str(),
+            property.value.to_ascii_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") => properties = Some(Vec::new()),
+            ("END", "VEVENT") => {
+                // one odd event shouldn't hide the rest of the calendar
+                match properties.take().map(|p| to_event(&p, default_tz)) {
+                    Some(Ok(Some(event))) => events.push(event),
+                    Some(Err(e)) => tracing::warn!("Skipping calendar event: {e}"),
+                    _ => {}
+                }
+                nested = 0;
+            }
+            ("BEGIN", _) if properties.is_some() => nested += 1,
+            ("END", _) if properties.is_some() => nested -= 1,
+            _ => {
+                if let Some(properties) = properties.as_mut().filter(|_| nested == 0) {
+                    properties.push(property);
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Returns `None` for events without a start, which can't be placed in the agenda.
+fn to_event(properties: &[Property], default_tz: Tz) -> anyhow::Result<Option<Event>> {
+    let get = |name: &str| properties.iter().find(|p| p.name == name);
+    let time_of = |p: &Property| {
+        parse_time(&p.value, p.param("TZID"), default_tz)
+            .map_err(|e| anyhow::anyhow!("{} '{}': {e}", p.name, p.value))
+    };
+
+    let Some(start) = get("DTSTART").map(time_of).transpose()? else {
+        return Ok(None);
+    };
+    let duration = match (get("DTEND"), get("DURATION")) {
+        (Some(end), _) => {
+            let end = time_of(end)?.in_tz(start.tz);
+            end - start.local
+        }
+        (None, Some(duration)) => parse_duration(&duration.value)?,
+        (None, None) if start.is_date() => TimeDelta::days(1),
+        (None, None) => TimeDelta::zero(),
+    };
+    if start.local.checked_add_signed(duration).is_none() {
+        anyhow::bail!("DURATION is too long");
+    }
+
+    let mut exdates = Vec::new();
+    for exdate in properties.iter().filter(|p| p.name == "EXDATE") {
+        for value in exdate.value.split(',') {
+            exdates.push(parse_time(value, exdate.param("TZID"), default_tz)?.in_tz(start.tz));
+        }
+    }
+
+    Ok(Some(Event {
+        uid: get("UID").map(|p| p.value.clone()).unwrap_or_default(),
+        summary: get("SUMMARY")
+            .map(|p| unescape(&p.value))
+            .unwrap_or_default(),
+        location: get("LOCATION")
+            .map(|p| unescape(&p.value))
+            .filter(|l| !l.trim().is_empty()),
+        rule: get("RRULE")
+            .map(|p| Rule::parse(&p.value, default_tz))
+            .transpose()?,
+        exdates,
+        recurrence_id: get("RECURRENCE-ID")
+            .map(time_of)
+            .transpose()?
+            .map(|t| t.in_tz(start.tz)),
+        cancelled: get("STATUS").is_some_and(|p| p.value.eq_ignore_ascii_case("CANCELLED")),
+        start,
+        duration,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_folded_escaped_and_zoned_events() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:call@example.com\r\n\
+            DTSTART;TZID=America/New_York:20250710T090000\r\n\
+            DTEND;TZID=America/New_York:20250710T093000\r\n\
+            SUMMARY:Call with the \r\n \\, New York office\r\n\
+            LOCATION:Room 1\\; by the lifts\r\n\
+            BEGIN:VALARM\r\n\
+            DESCRIPTION:Not the summary\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:holiday@example.com\r\n\
+            DTSTART;VALUE=DATE:20250711\r\n\
+            DTEND;VALUE=DATE:20250714\r\n\
+            SUMMARY:Holiday\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let events = parse_events(ics, chrono_tz::Europe::London).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let call = &events[0];
+        assert_eq!(call.summary, "Call with the , New York office");
+        assert_eq!(call.location.as_deref(), Some("Room 1; by the lifts"));
+        assert_eq!(call.duration, TimeDelta::minutes(30));
+        assert_eq!(
+            call.start
+                .in_tz(Some(chrono_tz::Europe::London))
+                .to_string(),
+            "2025-07-10 14:00:00"
+        );
+
+        let holiday = &events[1];
+        assert!(holiday.start.is_date());
+        assert_eq!(holiday.duration, TimeDelta::days(3));
+    }
+
+    #[test]
+    fn reads_durations() {
+        assert_eq!(parse_duration("PT1H30M").unwrap(), TimeDelta::minutes(90));
+        assert_eq!(parse_duration("P1W2D").unwrap(), TimeDelta::days(9));
+        assert_eq!(parse_duration("-PT15M").unwrap(), TimeDelta::minutes(-15));
+        assert!(parse_duration("1H").is_err());
+        assert!(parse_duration("P9223372036854775807W").is_err());
+        assert!(parse_duration("P15250284452W15250284452W").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+mod ics;
+mod recurrence;
+
+use super::DataSource;
+use crate::time::Localization;
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use ics::{Event, to_utc};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Titles of the days in the agenda, starting today.
+const DAYS: [&str; 2] = ["Today", "Tomorrow"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    pub calendars: Vec<FeedConfig>,
+}
+
+/// One calendar, read from either `url` or `path`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedConfig {
+    /// Shown next to its events when there are several calendars.
+    pub name: String,
+    /// Address of an ICS feed, `webcal://` ones included.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Path of a local `.ics` file.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+impl FeedConfig {
+    /// Calendar apps use `webcal://` for feeds that are plain `https://`.
+    fn url(&self) -> Option<String> {
+        self.url
+            .as_ref()
+            .map(|url| match url.strip_prefix("webcal://") {
+                Some(rest) => format!("https://{rest}"),
+                None => url.clone(),
+            })
+    }
+}
+
+/// Today's and tomorrow's events from every calendar.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Agenda {
+    pub days: Vec<AgendaDay>,
+    calendars: usize,
+}
+
+impl Agenda {
+    /// Whether to label events with their calendar.
+    #[must_use]
+    pub fn has_several_calendars(&self) -> bool {
+        self.calendars > 1
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaDay {
+    /// `Today` or `Tomorrow`.
+    pub title: &'static str,
+    /// e.g. `Thu 10 Jul`.
+    pub date: String,
+    /// All-day events first, then by start time.
+    pub events: Vec<AgendaEvent>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaEvent {
+    /// Local start time, e.g. `09:15`. `None` for all-day events and ones carrying on from
+    /// the day before.
+    pub start: Option<String>,
+    /// Local end time, if the event ends later the same day.
+    pub end: Option<String>,
+    pub summary: String,
+    pub location: Option<String>,
+    pub calendar: String,
+}
+
+/// Agenda from iCalendar feeds and files, with recurring events expanded.
+pub struct CalendarSource {
+    config: CalendarConfig,
+    localization: Localization,
+}
+
+impl DataSource for CalendarSource {
+    const NAME: &'static str = "calendar";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+    type Config = CalendarConfig;
+    type Output = Agenda;
+
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        if config.calendars.is_empty() {
+            anyhow::bail!("at least one calendar must be configured");
+        }
+        for feed in &config.calendars {
+            match (feed.url(), &feed.path) {
+                (Some(url), None) => {
+                    Url::parse(&url).map_err(|e| anyhow::anyhow!("{}: url {e}", feed.name))?;
+                }
+                (None, Some(_)) => {}
+                _ => anyhow::bail!("{}: set exactly one of url and path", feed.name),
+            }
+        }
+        Ok(Self {
+            config,
+            localization: *localization,
+        })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let mut calendars = Vec::new();
+        let mut errors = Vec::new();
+        for feed in &self.config.calendars {
+            match read_feed(client, feed)
+                .await
+                .and_then(|ics| ics::parse_events(&ics, self.localization.tz))
+            {
+                Ok(events) => calendars.push((feed.name.clone(), events)),
+                Err(e) => {
+                    tracing::warn!("Leaving out calendar {}: {e}", feed.name);
+                    errors.push(format!("{}: {e}", feed.name));
+                }
+            }
+        }
+        if errors.len() == self.config.calendars.len() {
+            anyhow::bail!("every calendar failed: {}", errors.join(", "));
+        }
+        Ok(to_agenda(
+            &calendars,
+            self.localization.today(),
+            &self.localization,
+        ))
+    }
+}
+
+async fn read_feed(client: &Client, feed: &FeedConfig) -> anyhow::Result<String> {
+    if let Some(url) = feed.url() {
+        return Ok(client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?);
+    }
+    let path = feed
+        .path
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("no url or path"))?;
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+}
+
+/// Every occurrence of the events up to `until`, with moved and cancelled ones accounted for.
+fn occurrences(events: &[Event], until: NaiveDateTime) -> Vec<(&Event, NaiveDateTime)> {
+    let replaced: HashSet<(&str, NaiveDateTime)> = events
+        .iter()
+        .filter_map(|e| Some((e.uid.as_str(), e.recurrence_id?)))
+        .collect();
+
+    events
+        .iter()
+        .filter(|e| !e.cancelled)
+        .flat_map(|e| {
+            let starts = match (&e.rule, e.recurrence_id) {
+                (Some(rule), None) => rule
+                    .occurrences(&e.start, until)
+                    .into_iter()
+                    .filter(|start| {
+                        !e.exdates.contains(start) && !replaced.contains(&(e.uid.as_str(), *start))
+                    })
+                    .collect(),
+                _ => vec![e.start.local],
+            };
+            starts.into_iter().map(move |start| (e, start))
+        })
+        .collect()
+}
+
+/// Whether an occurrence is on `date` and, if it starts that day, when.
+fn placement(
+    event: &Event,
+    start: NaiveDateTime,
+    date: NaiveDate,
+    tz: Tz,
+) -> Option<Option<DateTime<Utc>>> {
+    let Some(event_tz) = event.start.tz else {
+        // all-day events end the morning of their last date
+        let first = start.date();
+        let end = (start + event.duration).date();
+        return (first <= date && (date < end || date == first)).then_some(None);
+    };
+
+    let begins = to_utc(start, event_tz);
+    let ends = begins + event.duration;
+    let day_start = to_utc(date.and_time(NaiveTime::MIN), tz);
+    let day_end = to_utc((date + Days::new(1)).and_time(NaiveTime::MIN), tz);
+    if begins >= day_end || (begins < day_start && ends <= day_start) {
+        return None;
+    }
+    Some((begins >= day_start).then_some(begins))
+}
+
+fn to_agenda(
+    calendars: &[(String, Vec<Event>)],
+    today: NaiveDate,
+    localization: &Localization,
+) -> Agenda {
+    // a day past the end of the agenda covers events in timezones ahead of ours
+    let until = (today + Days::new(DAYS.len() as u64 + 1)).and_time(NaiveTime::MIN);
+    let occurrences: Vec<_> = calendars
+        .iter()
+        .flat_map(|(name, events)| {
+            occurrences(events, until)
+                .into_iter()
+                .map(move |(event, start)| (name, event, start))
+        })
+        .collect();
+
+    let days = DAYS
+        .iter()
+        .zip(0..)
+        .map(|(title, offset)| {
+            let date = today + Days::new(offset);
+            let mut events: Vec<_> = occurrences
+                .iter()
+                .filter_map(|&(name, event, start)| {
+                    let begins = placement(event, start, date, localization.tz)?;
+                    let ends = begins.map(|b| b + event.duration).filter(|e| {
+                        event.duration > chrono::TimeDelta::zero()
+                            && e.with_timezone(&localization.tz).date_naive() == date
+                    });
+                    Some((
+                        begins,
+                        AgendaEvent {
+                            start: begins.map(|t| localization.format(&t, "%H:%M")),
+                            end: ends.map(|t| localization.format(&t, "%H:%M")),
+                            summary: event.summary.clone(),
+                            location: event.location.clone(),
+                            calendar: name.clone(),
+                        },
+                    ))
+                })
+                .collect();
+            events.sort_by(|(a, a_event), (b, b_event)| {
+                a.cmp(b).then_with(|| a_event.summary.cmp(&b_event.summary))
+            });
+
+            AgendaDay {
+                title,
+                date: localization.format_date(date, "%a %-d %b"),
+                events: events.into_iter().map(|(_, event)| event).collect(),
+            }
+        })
+        .collect();
+
+    Agenda {
+        days,
+        calendars: calendars.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:standup
+DTSTART;TZID=Europe/London:20250612T091500
+DURATION:PT15M
+RRULE:FREQ=WEEKLY;BYDAY=TH,FR
+EXDATE;TZID=Europe/London:20250711T091500
+SUMMARY:Standup
+END:VEVENT
+BEGIN:VEVENT
+UID:standup
+RECURRENCE-ID;TZID=Europe/London:20250710T091500
+DTSTART;TZID=Europe/London:20250710T100000
+DTEND;TZID=Europe/London:20250710T101500
+SUMMARY:Standup (moved)
+END:VEVENT
+BEGIN:VEVENT
+UID:trip
+DTSTART;VALUE=DATE:20250709
+DTEND;VALUE=DATE:20250712
+SUMMARY:School trip
+END:VEVENT
+BEGIN:VEVENT
+UID:late-call
+DTSTART:20250710T230000Z
+DTEND:20250711T000000Z
+SUMMARY:Late call
+END:VEVENT
+BEGIN:VEVENT
+UID:new-york
+DTSTART;TZID=America/New_York:20250710T180000
+DTEND;TZID=America/New_York:20250710T190000
+SUMMARY:Call New York
+LOCATION:Zoom
+END:VEVENT
+BEGIN:VEVENT
+UID:dentist
+DTSTART;TZID=Europe/London:20250710T120000
+SUMMARY:Dentist
+STATUS:CANCELLED
+END:VEVENT
+END:VCALENDAR
+";
+
+    #[test]
+    fn builds_agenda_for_today_and_tomorrow() {
+        let localization = Localization::default();
+        let events = ics::parse_events(FAMILY, localization.tz).unwrap();
+        let today = NaiveDate::from_ymd_opt(2025, 7, 10).unwrap();
+
+        let agenda = to_agenda(&[("Family".to_string(), events)], today, &localization);
+        assert!(!agenda.has_several_calendars());
+        let days: Vec<_> = agenda
+            .days
+            .iter()
+            .map(|day| {
+                let events: Vec<_> = day
+                    .events
+                    .iter()
+                    .map(|e| {
+                        format!(
+                            "{}-{} {}",
+                            e.start.as_deref().unwrap_or(""),
+                            e.end.as_deref().unwrap_or(""),
+                            e.summary
+                        )
+                    })
+                    .collect();
+                (day.title, day.date.as_str(), events)
+            })
+            .collect();
+        assert_eq!(
+            days,
+            [
+                (
+                    "Today",
+                    "Thu 10 Jul",
+                    vec![
+                        "- School trip".to_string(),
+                        "10:00-10:15 Standup (moved)".to_string(),
+                        "23:00- Call New York".to_string(),
+                    ]
+                ),
+                (
+                    "Tomorrow",
+                    "Fri 11 Jul",
+                    vec![
+                        "- School trip".to_string(),
+                        "00:00-01:00 Late call".to_string(),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_exactly_one_source_per_calendar() {
+        let config = |feed: &str| -> CalendarConfig {
+            toml::from_str(&format!("calendars = [{{ name = \"Family\", {feed} }}]")).unwrap()
+        };
+        let localization = Localization::default();
+        assert!(
+            CalendarSource::new(
+                config(r#"url = "webcal://example.com/family.ics""#),
+                &localization
+            )
+            .is_ok()
+        );
+        assert!(CalendarSource::new(config(r#"path = "family.ics""#), &localization).is_ok());
+        assert!(
+            CalendarSource::new(
+                config(r#"url = "https://example.com/a.ics", path = "family.ics""#),
+                &localization
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_out_failing_calendars() {
+        let path = std::env::temp_dir().join("inky_display_calendar_test_family.ics");
+        std::fs::write(&path, FAMILY).unwrap();
+        let config: CalendarConfig = toml::from_str(&format!(
+            r#"
+            calendars = [
+                {{ name = "Family", path = "{}" }},
+                {{ name = "Work", path = "/nonexistent/work.ics" }},
+            ]
+            "#,
+            path.display()
+        ))
+        .unwrap();
+        let localization = Localization::default();
+        let source = CalendarSource::new(config.clone(), &localization).unwrap();
+        assert!(source.fetch(&Client::new()).await.is_ok());
+
+        let mut config = config;
+        config.calendars.remove(0);
+        let source = CalendarSource::new(config, &localization).unwrap();
+        let error = source.fetch(&Client::new()).await.unwrap_err().to_string();
+        assert!(error.contains("Work"), "{error}");
+    }
+}
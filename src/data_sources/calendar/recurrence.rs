@@ -0,0 +1,348 @@
+//! Expands `RRULE`s into the starts of their occurrences.
+
+use super::ics::{Time, parse_time};
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, Weekday};
+use chrono_tz::Tz;
+
+/// Stops runaway rules, e.g. a daily rule started a century ago with no end.
+const MAX_PERIODS: u32 = 50_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A recurrence rule. `BYSETPOS`, `BYWEEKNO`, `BYYEARDAY` and sub-daily frequencies aren't
+/// supported and are rejected, rather than showing events on the wrong days.
+#[derive(Debug, Clone)]
+pub(super) struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<Time>,
+    /// Weekdays, with an ordinal within the month for monthly and yearly rules, e.g. `-1FR`.
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+impl Rule {
+    pub fn parse(value: &str, default_tz: Tz) -> anyhow::Result<Self> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+        };
+        let mut frequency = None;
+        for part in value.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("invalid RRULE part '{part}'"))?;
+            let list = || value.split(',');
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => anyhow::bail!("unsupported RRULE frequency '{other}'"),
+                    });
+                }
+                "INTERVAL" => rule.interval = value.parse::<u32>()?.max(1),
+                "COUNT" => rule.count = Some(value.parse()?),
+                "UNTIL" => rule.until = Some(parse_time(value, None, default_tz)?),
+                "BYDAY" => {
+                    rule.by_day = list().map(parse_weekday).collect::<anyhow::Result<_>>()?;
+                }
+                "BYMONTHDAY" => {
+                    rule.by_month_day = list().map(str::parse).collect::<Result<_, _>>()?;
+                }
+                "BYMONTH" => rule.by_month = list().map(str::parse).collect::<Result<_, _>>()?,
+                "WKST" => {}
+                other => anyhow::bail!("unsupported RRULE part '{other}'"),
+            }
+        }
+        rule.frequency = frequency.ok_or_else(|| anyhow::anyhow!("RRULE has no FREQ"))?;
+        Ok(rule)
+    }
+
+    /// Starts of the occurrences from `start` up to `until`, in the local time of `start`.
+    pub fn occurrences(&self, start: &Time, until: NaiveDateTime) -> Vec<NaiveDateTime> {
+        let first = start.local;
+        let rule_until = self.until.map(|u| u.in_tz(start.tz));
+
+        let mut starts = Vec::new();
+        let mut seen = 0;
+        for period in 0..MAX_PERIODS {
+            // past what a date can hold, e.g. with a huge INTERVAL
+            let Some(dates) = period
+                .checked_mul(self.interval)
+                .and_then(|offset| self.dates_in_period(first.date(), offset))
+            else {
+                return starts;
+            };
+            for date in dates {
+                let occurrence = date.and_time(first.time());
+                if occurrence < first {
+                    continue;
+                }
+                if occurrence > until
+                    || rule_until.is_some_and(|u| occurrence > u)
+                    || self.count.is_some_and(|c| seen >= c)
+                {
+                    return starts;
+                }
+                seen += 1;
+                starts.push(occurrence);
+            }
+        }
+        starts
+    }
+
+    /// Candidate dates in the period `offset` days, weeks, months or years after `first`'s,
+    /// in order, or `None` if the period is out of the range of dates.
+    fn dates_in_period(&self, first: NaiveDate, offset: u32) -> Option<Vec<NaiveDate>> {
+        let mut dates = match self.frequency {
+            Frequency::Daily => Some(first.checked_add_days(Days::new(offset.into()))?)
+                .filter(|d| self.by_month.is_empty() || self.by_month.contains(&d.month()))
+                .filter(|d| {
+                    self.by_day.is_empty() || self.by_day.iter().any(|(_, w)| *w == d.weekday())
+                })
+                .into_iter()
+                .collect(),
+            Frequency::Weekly => {
+                let monday = first
+                    .checked_sub_days(Days::new(first.weekday().num_days_from_monday().into()))?
+                    .checked_add_days(Days::new(u64::from(offset) * 7))?;
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![first.weekday()]
+                } else {
+                    self.by_day.iter().map(|(_, w)| *w).collect()
+                };
+                weekdays
+                    .into_iter()
+                    .filter_map(|w| {
+                        monday.checked_add_days(Days::new(w.num_days_from_monday().into()))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = (first.year() * 12 + first.month0() as i32)
+                    .checked_add(i32::try_from(offset).ok()?)?;
+                let (year, month) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                self.dates_in_month(year, month, first.day())
+            }
+            Frequency::Yearly => {
+                let year = first.year().checked_add(i32::try_from(offset).ok()?)?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                let months = if self.by_month.is_empty() {
+                    vec![first.month()]
+                } else {
+                    self.by_month.clone()
+                };
+                months
+                    .into_iter()
+                    .flat_map(|month| self.dates_in_month(year, month, first.day()))
+                    .collect()
+            }
+        };
+        dates.sort();
+        dates.dedup();
+        Some(dates)
+    }
+
+    fn dates_in_month(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let Some(last_day) = last_day_of_month(year, month) else {
+            return Vec::new();
+        };
+        let by_month_day: Vec<NaiveDate> = self
+            .by_month_day
+            .iter()
+            .filter_map(|&day| {
+                let day = if day < 0 {
+                    last_day as i32 + 1 + day
+                } else {
+                    day
+                };
+                NaiveDate::from_ymd_opt(year, month, u32::try_from(day).ok()?)
+            })
+            .collect();
+
+        match (self.by_day.is_empty(), self.by_month_day.is_empty()) {
+            // months without that day, e.g. the 31st, are skipped
+            (true, true) => NaiveDate::from_ymd_opt(year, month, default_day)
+                .into_iter()
+                .collect(),
+            (true, false) => by_month_day,
+            (false, true) => self
+                .by_day
+                .iter()
+                .flat_map(|&(ordinal, weekday)| weekdays_in_month(year, month, weekday, ordinal))
+                .collect(),
+            (false, false) => by_month_day
+                .into_iter()
+                .filter(|d| self.by_day.iter().any(|(_, w)| *w == d.weekday()))
+                .collect(),
+        }
+    }
+}
+
+fn parse_weekday(value: &str) -> anyhow::Result<(Option<i32>, Weekday)> {
+    let value = value.trim();
+    if !value.is_ascii() {
+        anyhow::bail!("invalid BYDAY '{value}'");
+    }
+    let split = value.len().saturating_sub(2);
+    let (ordinal, day) = value.split_at(split);
+    let weekday = match day.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => anyhow::bail!("invalid BYDAY '{value}'"),
+    };
+    let ordinal = (!ordinal.is_empty())
+        .then(|| ordinal.trim_start_matches('+').parse())
+        .transpose()?;
+    Ok((ordinal, weekday))
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<u32> {
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    }?;
+    next.pred_opt().map(|d| d.day())
+}
+
+/// Every `weekday` in the month, or just the `ordinal`th one, counting back from the end of
+/// the month when negative.
+fn weekdays_in_month(
+    year: i32,
+    month: u32,
+    weekday: Weekday,
+    ordinal: Option<i32>,
+) -> Vec<NaiveDate> {
+    let all: Vec<NaiveDate> = (1..=5)
+        .filter_map(|n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n))
+        .collect();
+    match ordinal {
+        None => all,
+        Some(n) if n > 0 => all.get(n as usize - 1).copied().into_iter().collect(),
+        Some(n) => all
+            .len()
+            .checked_sub(n.unsigned_abs() as usize)
+            .and_then(|i| all.get(i))
+            .copied()
+            .into_iter()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(rule: &str, start: &str, until: &str) -> Vec<String> {
+        let tz = chrono_tz::Europe::London;
+        let start = parse_time(start, None, tz).unwrap();
+        let until = parse_time(until, None, tz).unwrap().local;
+        Rule::parse(rule, tz)
+            .unwrap()
+            .occurrences(&start, until)
+            .iter()
+            .map(|t| t.format("%a %Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn expands_rules() {
+        assert_eq!(
+            expand(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,TH;COUNT=4",
+                "20250701T183000",
+                "20251231T000000"
+            ),
+            [
+                "Tue 2025-07-01",
+                "Thu 2025-07-03",
+                "Tue 2025-07-15",
+                "Thu 2025-07-17"
+            ]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;BYDAY=-1FR",
+                "20250131T090000",
+                "20250501T000000"
+            ),
+            [
+                "Fri 2025-01-31",
+                "Fri 2025-02-28",
+                "Fri 2025-03-28",
+                "Fri 2025-04-25"
+            ]
+        );
+        // months without a 31st are skipped
+        assert_eq!(
+            expand(
+                "FREQ=MONTHLY;UNTIL=20250601T000000Z",
+                "20250131T090000",
+                "20251231T000000"
+            ),
+            ["Fri 2025-01-31", "Mon 2025-03-31", "Sat 2025-05-31"]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU",
+                "20250330T100000",
+                "20270101T000000"
+            ),
+            ["Sun 2025-03-30", "Sun 2026-03-29"]
+        );
+        assert_eq!(
+            expand(
+                "FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR",
+                "20250704T080000",
+                "20250708T000000"
+            ),
+            ["Fri 2025-07-04", "Mon 2025-07-07"]
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        let tz = chrono_tz::Europe::London;
+        assert!(Rule::parse("FREQ=HOURLY", tz).is_err());
+        assert!(Rule::parse("FREQ=MONTHLY;BYSETPOS=-1;BYDAY=MO,TU", tz).is_err());
+        assert!(Rule::parse("INTERVAL=2", tz).is_err());
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_date_range() {
+        for frequency in ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            assert_eq!(
+                expand(
+                    &format!("FREQ={frequency};INTERVAL=4000000000"),
+                    "20250701T183000",
+                    "20991231T000000"
+                ),
+                ["Tue 2025-07-01"],
+                "{frequency}"
+            );
+        }
+    }
+}
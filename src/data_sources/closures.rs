@@ -0,0 +1,143 @@
+use super::DataSource;
+use super::tube::{LineStatus, TflLineResponse, TubeConfig, tfl_get, to_line_statuses};
+use crate::time::Localization;
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Planned closures of the configured lines over the coming weekend.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WeekendClosures {
+    /// Saturday and Sunday of the weekend, e.g. `7-8 Jun`.
+    pub dates: String,
+    /// Only the lines with something planned, worst first.
+    pub lines: Vec<LineStatus>,
+}
+
+/// Whether the coming weekend is close enough to be worth showing, i.e. from Thursday onward.
+#[must_use]
+pub fn weekend_is_near(today: NaiveDate) -> bool {
+    matches!(
+        today.weekday(),
+        Weekday::Thu | Weekday::Fri | Weekday::Sat | Weekday::Sun
+    )
+}
+
+/// The Saturday and Sunday of the coming weekend, or the current one during a weekend.
+fn weekend_of(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let saturday = if today.weekday() == Weekday::Sun {
+        today - TimeDelta::days(1)
+    } else {
+        today
+            + TimeDelta::days(i64::from(
+                (Weekday::Sat.num_days_from_monday() + 7 - today.weekday().num_days_from_monday())
+                    % 7,
+            ))
+    };
+    (saturday, saturday + TimeDelta::days(1))
+}
+
+/// Planned weekend closures from TfL, for the modes and lines of the `tube` section unless set
+/// otherwise.
+pub struct ClosuresSource {
+    config: TubeConfig,
+    localization: Localization,
+}
+
+impl DataSource for ClosuresSource {
+    const NAME: &'static str = "tube_closures";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+    type Config = TubeConfig;
+    type Output = WeekendClosures;
+
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        // same settings as the live status, so the same checks
+        super::TubeSource::new(config.clone(), localization)?;
+        Ok(Self {
+            config,
+            localization: *localization,
+        })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let (saturday, sunday) = weekend_of(self.localization.today());
+
+        // the date range status is only available by line id, so look those up first
+        let lines: Vec<TflLineResponse> = tfl_get(
+            client,
+            &self.config.api_key,
+            &format!("Line/Mode/{}", self.config.modes.join(",")),
+        )
+        .await?;
+        let ids: Vec<_> = lines
+            .iter()
+            .filter(|line| self.config.wants(line))
+            .map(|line| line.id.as_str())
+            .collect();
+        if ids.is_empty() {
+            anyhow::bail!(
+                "none of the configured lines run on {:?}",
+                self.config.modes
+            );
+        }
+
+        let path = format!(
+            "Line/{}/Status/{}T00:00:00/to/{}T23:59:59",
+            ids.join(","),
+            saturday.format("%Y-%m-%d"),
+            sunday.format("%Y-%m-%d"),
+        );
+        let response: Vec<TflLineResponse> = tfl_get(client, &self.config.api_key, &path).await?;
+
+        Ok(WeekendClosures {
+            dates: if saturday.month() == sunday.month() {
+                format!(
+                    "{}-{}",
+                    saturday.day(),
+                    self.localization.format_date(sunday, "%-d %b")
+                )
+            } else {
+                format!(
+                    "{}-{}",
+                    self.localization.format_date(saturday, "%-d %b"),
+                    self.localization.format_date(sunday, "%-d %b")
+                )
+            },
+            lines: to_line_statuses(&self.config, response)
+                .into_iter()
+                .filter(|line| !line.is_good_service())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_coming_weekend() {
+        let weekend = (date("2025-06-07"), date("2025-06-08"));
+        for day in ["2025-06-02", "2025-06-05", "2025-06-07", "2025-06-08"] {
+            assert_eq!(weekend_of(date(day)), weekend, "{day}");
+        }
+        assert_eq!(
+            weekend_of(date("2025-06-09")),
+            (date("2025-06-14"), date("2025-06-15"))
+        );
+    }
+
+    #[test]
+    fn shown_from_thursday() {
+        let near: Vec<_> = (2..=9)
+            .map(|d| weekend_is_near(date(&format!("2025-06-0{d}"))))
+            .collect();
+        assert_eq!(near, [false, false, false, true, true, true, true, false]);
+    }
+}
@@ -1,22 +1,191 @@
-use chrono::{DateTime, Local, NaiveDate, Utc};
+use super::DataSource;
+use crate::time::Localization;
+use chrono::{DateTime, TimeDelta, Utc};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use toml::{Table, Value};
 
-use crate::SERVER_CONFIG;
+const API_BASE: &str = "https://api.football-data.org/v4";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamConfig {
+    /// football-data.org team id, e.g. 57 for Arsenal.
+    pub id: u32,
+    /// Name used to label the team's fixtures, defaults to the short name from the API.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+// Defaults match what the dashboard showed before teams were configurable.
+fn default_teams() -> Vec<TeamConfig> {
+    vec![TeamConfig {
+        id: 57,
+        name: Some("Arsenal".to_string()),
+    }]
+}
+
+fn default_title() -> String {
+    "Arsenal FC".to_string()
+}
+
+fn default_window_days() -> u32 {
+    7
+}
+
+fn default_finished_count() -> usize {
+    2
+}
+
+fn default_scheduled_count() -> usize {
+    3
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FootballConfig {
+    pub api_key: String,
+    #[serde(default = "default_teams")]
+    pub teams: Vec<TeamConfig>,
+    /// Heading shown above the fixtures.
+    #[serde(default = "default_title")]
+    pub title: String,
+    /// Competition codes to include, e.g. `["PL", "CL"]`. Empty means all.
+    #[serde(default)]
+    pub competitions: Vec<String>,
+    #[serde(default = "default_window_days")]
+    pub lookback_days: u32,
+    #[serde(default = "default_window_days")]
+    pub lookahead_days: u32,
+    /// How many recent results to show.
+    #[serde(default = "default_finished_count")]
+    pub finished_count: usize,
+    /// How many upcoming fixtures to show.
+    #[serde(default = "default_scheduled_count")]
+    pub scheduled_count: usize,
+}
 
 const FMT_STR: &str = "%d %b %H:%M";
 
-#[derive(Debug, Clone, Default)]
+/// How long after kick-off a match still counts as in progress when the API has not caught up
+/// with its status yet.
+const MATCH_LENGTH: TimeDelta = TimeDelta::minutes(150);
+
+/// Fixtures for the followed teams, with the heading to show them under.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Fixtures {
+    pub title: String,
+    pub matches: Vec<Match>,
+}
+
+impl Fixtures {
+    /// Whether the matches are for more than one followed team, so need labelling.
+    #[must_use]
+    pub fn has_several_teams(&self) -> bool {
+        self.matches
+            .first()
+            .is_some_and(|first| self.matches.iter().any(|m| m.team_id != first.team_id))
+    }
+
+    /// The match being played at `now`, if any.
+    #[must_use]
+    pub fn in_progress(&self, now: DateTime<Utc>) -> Option<&Match> {
+        self.matches.iter().find(|m| m.is_in_progress(now))
+    }
+
+    /// The match to feature on the match-day page: the one in progress, or else the latest result.
+    #[must_use]
+    pub fn featured(&self, now: DateTime<Utc>) -> Option<&Match> {
+        self.in_progress(now).or_else(|| {
+            self.matches
+                .iter()
+                .rev()
+                .find(|m| m.status == MatchStatus::Finished)
+        })
+    }
+
+    /// When the next match yet to start kicks off.
+    #[must_use]
+    pub fn next_kickoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.matches
+            .iter()
+            .filter(|m| m.status == MatchStatus::Scheduled && m.kickoff > now)
+            .map(|m| m.kickoff)
+            .min()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    #[default]
+    Scheduled,
+    Live,
+    HalfTime,
+    Finished,
+}
+
+impl MatchStatus {
+    fn from_api(status: &str) -> Self {
+        match status {
+            "IN_PLAY" | "EXTRA_TIME" | "PENALTY_SHOOTOUT" => Self::Live,
+            "PAUSED" => Self::HalfTime,
+            "FINISHED" | "AWARDED" => Self::Finished,
+            _ => Self::Scheduled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Match {
+    /// Id of the followed team this match was fetched for.
+    pub team_id: u32,
+    /// Configured name of the followed team.
+    pub team: String,
     pub home_team: String,
     pub home_crest_url: String,
     pub away_team: String,
     pub away_crest_url: String,
+    pub kickoff: DateTime<Utc>,
     pub datetime_string: String,
+    pub status: MatchStatus,
+    /// Minute of play while live, e.g. `67'` or `45+2'`, when the API provides it.
+    pub minute: Option<String>,
+    /// Current score once the match has started.
     pub score: Option<String>,
     pub competition: String,
 }
 
+impl Match {
+    /// Whether the match is being played, including just after kick-off before the API has
+    /// marked it live.
+    #[must_use]
+    pub fn is_in_progress(&self, now: DateTime<Utc>) -> bool {
+        match self.status {
+            MatchStatus::Live | MatchStatus::HalfTime => true,
+            MatchStatus::Scheduled => self.kickoff <= now && now < self.kickoff + MATCH_LENGTH,
+            MatchStatus::Finished => false,
+        }
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.status == MatchStatus::Finished
+    }
+
+    /// Short state shown in place of the date while the match is on, e.g. `67'` or `HT`.
+    #[must_use]
+    pub fn live_label(&self) -> Option<String> {
+        match self.status {
+            MatchStatus::Live => Some(self.minute.clone().unwrap_or_else(|| "LIVE".to_string())),
+            MatchStatus::HalfTime => Some("HT".to_string()),
+            MatchStatus::Scheduled | MatchStatus::Finished => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct FootballDataResponse {
     matches: Vec<FootballMatch>,
@@ -24,6 +193,7 @@ struct FootballDataResponse {
 
 #[derive(Debug, Deserialize)]
 struct FootballMatch {
+    id: u64,
     #[serde(rename = "homeTeam")]
     home_team: FootballTeam,
     #[serde(rename = "awayTeam")]
@@ -33,10 +203,16 @@ struct FootballMatch {
     score: FootballScore,
     competition: FootballCompetition,
     status: String,
+    /// Only present while the match is being played, as either a number or a string.
+    #[serde(default)]
+    minute: Option<serde_json::Value>,
+    #[serde(default, rename = "injuryTime")]
+    injury_time: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
 struct FootballTeam {
+    id: u32,
     #[serde(rename = "shortName")]
     short_name: String,
     crest: String,
@@ -78,89 +254,337 @@ fn competition_code_to_name(code: &str) -> &'static str {
     }
 }
 
-fn format_match_date(utc_date: &str) -> String {
-    // Parse ISO 8601 format like "2025-01-20T15:00:00Z" and extract "Jan 20"
-    const MONTHS: [&str; 12] = [
-        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
-    ];
+/// Recent results and upcoming fixtures for the configured teams from football-data.org.
+pub struct FootballSource {
+    config: FootballConfig,
+    localization: Localization,
+}
+
+impl DataSource for FootballSource {
+    const NAME: &'static str = "football";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
 
-    let date_part = utc_date.split('T').next().unwrap_or(utc_date);
-    let parts: Vec<&str> = date_part.split('-').collect();
+    type Config = FootballConfig;
+    type Output = Fixtures;
 
-    if parts.len() >= 3
-        && let (Ok(month), Ok(day)) = (parts[1].parse::<usize>(), parts[2].parse::<u32>())
-        && (1..=12).contains(&month)
-    {
-        return format!("{} {}", MONTHS[month - 1], day);
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        if config.api_key.trim().is_empty() {
+            anyhow::bail!("api_key must not be empty");
+        }
+        if config.teams.is_empty() {
+            anyhow::bail!("at least one team must be configured");
+        }
+        Ok(Self {
+            config,
+            localization: *localization,
+        })
     }
 
-    "???".to_string()
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        fetch_matches(client, &self.config, &self.localization).await
+    }
 }
 
-pub async fn fetch_arsenal_matches(client: &Client) -> Result<Vec<Match>, anyhow::Error> {
-    // Fetch both scheduled and finished matches
-    let now = Local::now().date_naive();
-    let date_from = now - chrono::TimeDelta::days(7);
-    let date_to = now + chrono::TimeDelta::days(7);
-    let url = format!(
-        "https://api.football-data.org/v4/teams/57/matches?dateFrom={date_from}&dateTo={date_to}&status=SCHEDULED,FINISHED&limit=10"
-    );
-
-    let response: FootballDataResponse = client
-        .get(url)
-        .header("X-Auth-Token", SERVER_CONFIG.football_api_key.clone())
+/// Makes an authenticated request to the football-data.org API, `path` being relative to `/v4/`.
+pub(super) async fn football_data_get<T: DeserializeOwned>(
+    client: &Client,
+    api_key: &str,
+    path: &str,
+) -> Result<T, anyhow::Error> {
+    Ok(client
+        .get(format!("{API_BASE}/{path}"))
+        .header("X-Auth-Token", api_key)
         .send()
         .await?
+        .error_for_status()?
         .json()
-        .await?;
+        .await?)
+}
 
-    // Separate finished and scheduled matches
-    let mut finished: Vec<_> = response
-        .matches
-        .iter()
-        .filter(|m| m.status == "FINISHED")
+/// Lets the other football-data.org sections leave out `api_key` and use the one from
+/// `[football]`, which is also where `FOOTBALL_API_KEY` goes.
+pub(super) fn share_api_key(sections: &mut Table, football_sections: &[&str]) {
+    let Some(key) = sections
+        .get(FootballSource::NAME)
+        .and_then(|football| football.get("api_key"))
+        .cloned()
+    else {
+        return;
+    };
+    for name in football_sections {
+        if let Some(section) = sections.get_mut(*name).and_then(Value::as_table_mut) {
+            section.entry("api_key").or_insert_with(|| key.clone());
+        }
+    }
+}
+
+async fn fetch_team_matches(
+    client: &Client,
+    config: &FootballConfig,
+    localization: &Localization,
+    team: &TeamConfig,
+) -> Result<Vec<FootballMatch>, anyhow::Error> {
+    // Fetch scheduled, live and finished matches
+    let now = localization.today();
+    let date_from = now - chrono::TimeDelta::days(config.lookback_days.into());
+    let date_to = now + chrono::TimeDelta::days(config.lookahead_days.into());
+    let mut path = format!(
+        "teams/{}/matches?dateFrom={date_from}&dateTo={date_to}&status=SCHEDULED,TIMED,IN_PLAY,PAUSED,EXTRA_TIME,PENALTY_SHOOTOUT,FINISHED",
+        team.id
+    );
+    if !config.competitions.is_empty() {
+        path.push_str(&format!("&competitions={}", config.competitions.join(",")));
+    }
+
+    let response: FootballDataResponse = football_data_get(client, &config.api_key, &path).await?;
+
+    Ok(response.matches)
+}
+
+async fn fetch_matches(
+    client: &Client,
+    config: &FootballConfig,
+    localization: &Localization,
+) -> Result<Fixtures, anyhow::Error> {
+    let mut team_matches = Vec::new();
+    for team in &config.teams {
+        team_matches.push((
+            team,
+            fetch_team_matches(client, config, localization, team).await?,
+        ));
+    }
+
+    Ok(Fixtures {
+        title: config.title.clone(),
+        matches: select_matches(config, localization, team_matches),
+    })
+}
+
+impl FootballMatch {
+    fn to_match(&self, team: &TeamConfig, localization: &Localization) -> Match {
+        let followed = if self.home_team.id == team.id {
+            &self.home_team
+        } else {
+            &self.away_team
+        };
+
+        let status = MatchStatus::from_api(&self.status);
+        Match {
+            team_id: team.id,
+            team: team
+                .name
+                .clone()
+                .unwrap_or_else(|| followed.short_name.clone()),
+            home_team: self.home_team.short_name.clone(),
+            home_crest_url: self.home_team.crest.clone(),
+            away_team: self.away_team.short_name.clone(),
+            away_crest_url: self.away_team.crest.clone(),
+            kickoff: self.utc_datetime,
+            datetime_string: localization.format(&self.utc_datetime, FMT_STR),
+            status,
+            minute: (status == MatchStatus::Live)
+                .then(|| self.minute_label())
+                .flatten(),
+            score: (status != MatchStatus::Scheduled).then(|| {
+                format!(
+                    "{}-{}",
+                    self.score.full_time.home.unwrap_or(0),
+                    self.score.full_time.away.unwrap_or(0)
+                )
+            }),
+            competition: competition_code_to_name(&self.competition.code).to_string(),
+        }
+    }
+
+    fn minute_label(&self) -> Option<String> {
+        let minute = match self.minute.as_ref()? {
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::String(s) if !s.is_empty() => s.clone(),
+            _ => return None,
+        };
+        Some(match self.injury_time {
+            Some(extra) if extra > 0 => format!("{minute}+{extra}'"),
+            _ => format!("{minute}'"),
+        })
+    }
+}
+
+/// Merges the matches of every followed team, dropping duplicates where two followed teams
+/// play each other, and keeps the most recent results, any live matches and the nearest
+/// fixtures.
+fn select_matches(
+    config: &FootballConfig,
+    localization: &Localization,
+    team_matches: Vec<(&TeamConfig, Vec<FootballMatch>)>,
+) -> Vec<Match> {
+    let mut seen = std::collections::HashSet::new();
+    let mut all: Vec<(&TeamConfig, FootballMatch)> = team_matches
+        .into_iter()
+        .flat_map(|(team, matches)| matches.into_iter().map(move |m| (team, m)))
+        .filter(|(_, m)| {
+            config.competitions.is_empty() || config.competitions.contains(&m.competition.code)
+        })
+        .filter(|(_, m)| seen.insert(m.id))
         .collect();
-    let mut scheduled: Vec<_> = response
-        .matches
+    all.sort_by_key(|(_, m)| m.utc_datetime);
+
+    // Separate finished, live and scheduled matches
+    let status = |m: &FootballMatch| MatchStatus::from_api(&m.status);
+    let finished: Vec<_> = all
         .iter()
-        .filter(|m| m.status == "SCHEDULED" || m.status == "TIMED")
+        .filter(|(_, m)| status(m) == MatchStatus::Finished)
         .collect();
+    let live = all
+        .iter()
+        .filter(|(_, m)| matches!(status(m), MatchStatus::Live | MatchStatus::HalfTime));
+    let scheduled = all
+        .iter()
+        .filter(|(_, m)| status(m) == MatchStatus::Scheduled);
+
+    let skip = finished.len().saturating_sub(config.finished_count);
+    finished
+        .into_iter()
+        .skip(skip)
+        .chain(live)
+        .chain(scheduled.take(config.scheduled_count))
+        .map(|(team, m)| m.to_match(team, localization))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn football_match(id: u64, home: u32, away: u32, date: &str, status: &str) -> FootballMatch {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "homeTeam": { "id": home, "shortName": format!("Team {home}"), "crest": "" },
+            "awayTeam": { "id": away, "shortName": format!("Team {away}"), "crest": "" },
+            "utcDate": date,
+            "score": { "fullTime": { "home": 1, "away": 0 } },
+            "competition": { "code": "PL" },
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn merges_and_dedupes_teams() {
+        let config: FootballConfig = toml::from_str(
+            r#"
+            api_key = "key"
+            finished_count = 1
+            scheduled_count = 2
+            teams = [{ id = 57, name = "Arsenal" }, { id = 61 }]
+            "#,
+        )
+        .unwrap();
+        let (arsenal, chelsea) = (&config.teams[0], &config.teams[1]);
+
+        let matches = select_matches(
+            &config,
+            &Localization::default(),
+            vec![
+                (
+                    arsenal,
+                    vec![
+                        football_match(1, 57, 1, "2025-01-01T15:00:00Z", "FINISHED"),
+                        football_match(2, 57, 61, "2025-01-10T15:00:00Z", "SCHEDULED"),
+                    ],
+                ),
+                (
+                    chelsea,
+                    vec![
+                        football_match(3, 2, 61, "2025-01-02T15:00:00Z", "FINISHED"),
+                        football_match(2, 57, 61, "2025-01-10T15:00:00Z", "SCHEDULED"),
+                        football_match(4, 61, 3, "2025-01-14T15:00:00Z", "TIMED"),
+                    ],
+                ),
+            ],
+        );
 
-    // Sort finished by date descending (most recent first)
-    finished.sort_by(|a, b| b.utc_datetime.cmp(&a.utc_datetime));
-    // Sort scheduled by date ascending (nearest first)
-    scheduled.sort_by(|a, b| a.utc_datetime.cmp(&b.utc_datetime));
-
-    // Take last 2 finished + next 3 scheduled = 5 matches
-    let mut matches: Vec<Match> = Vec::new();
-
-    for m in finished.into_iter().take(2).rev() {
-        matches.push(Match {
-            home_team: m.home_team.short_name.clone(),
-            home_crest_url: m.home_team.crest.clone(),
-            away_team: m.away_team.short_name.clone(),
-            away_crest_url: m.away_team.crest.clone(),
-            datetime_string: m.utc_datetime.format(FMT_STR).to_string(),
-            score: Some(format!(
-                "{}-{}",
-                m.score.full_time.home.unwrap_or(0),
-                m.score.full_time.away.unwrap_or(0)
-            )),
-            competition: competition_code_to_name(&m.competition.code).to_string(),
-        });
-    }
-
-    for m in scheduled.into_iter().take(3) {
-        matches.push(Match {
-            home_team: m.home_team.short_name.clone(),
-            home_crest_url: m.home_team.crest.clone(),
-            away_team: m.away_team.short_name.clone(),
-            away_crest_url: m.away_team.crest.clone(),
-            datetime_string: m.utc_datetime.format(FMT_STR).to_string(),
-            score: None,
-            competition: competition_code_to_name(&m.competition.code).to_string(),
-        });
-    }
-
-    Ok(matches)
+        let selected: Vec<_> = matches
+            .iter()
+            .map(|m| (m.team.as_str(), m.score.is_some()))
+            .collect();
+        assert_eq!(
+            selected,
+            [("Team 61", true), ("Arsenal", false), ("Team 61", false)]
+        );
+    }
+
+    #[test]
+    fn surfaces_live_matches() {
+        let config: FootballConfig = toml::from_str(r#"api_key = "key""#).unwrap();
+        let mut live = football_match(2, 57, 1, "2025-01-10T15:00:00Z", "IN_PLAY");
+        live.minute = Some(serde_json::json!(45));
+        live.injury_time = Some(2);
+
+        let matches = select_matches(
+            &config,
+            &Localization::default(),
+            vec![(
+                &config.teams[0],
+                vec![
+                    football_match(1, 57, 1, "2025-01-01T15:00:00Z", "FINISHED"),
+                    live,
+                    football_match(3, 57, 1, "2025-01-10T17:30:00Z", "PAUSED"),
+                    football_match(4, 57, 1, "2025-01-14T15:00:00Z", "TIMED"),
+                ],
+            )],
+        );
+        let labels: Vec<_> = matches.iter().map(Match::live_label).collect();
+        assert_eq!(
+            labels,
+            [
+                None,
+                Some("45+2'".to_string()),
+                Some("HT".to_string()),
+                None
+            ]
+        );
+        assert_eq!(matches[1].score.as_deref(), Some("1-0"));
+
+        let fixtures = Fixtures {
+            title: String::new(),
+            matches,
+        };
+        let now = "2025-01-10T16:00:00Z".parse().unwrap();
+        assert_eq!(
+            fixtures.in_progress(now).map(|m| m.kickoff.to_string()),
+            Some("2025-01-10 15:00:00 UTC".to_string())
+        );
+        assert_eq!(
+            fixtures.next_kickoff(now),
+            Some("2025-01-14T15:00:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn kicked_off_match_counts_as_in_progress() {
+        let kickoff: DateTime<Utc> = "2025-01-10T15:00:00Z".parse().unwrap();
+        let m = Match {
+            kickoff,
+            ..Match::default()
+        };
+        assert!(!m.is_in_progress(kickoff - TimeDelta::minutes(1)));
+        assert!(m.is_in_progress(kickoff + TimeDelta::minutes(5)));
+        assert!(!m.is_in_progress(kickoff + MATCH_LENGTH));
+    }
+
+    #[test]
+    fn kickoff_shown_in_local_time() {
+        let team = TeamConfig { id: 57, name: None };
+        let localization = Localization::default();
+        let kickoff = |date| {
+            football_match(1, 57, 1, date, "TIMED")
+                .to_match(&team, &localization)
+                .datetime_string
+        };
+
+        assert_eq!(kickoff("2025-03-29T15:00:00Z"), "29 Mar 15:00");
+        assert_eq!(kickoff("2025-03-30T15:00:00Z"), "30 Mar 16:00");
+        assert_eq!(kickoff("2025-10-25T14:00:00Z"), "25 Oct 15:00");
+        assert_eq!(kickoff("2025-10-26T15:00:00Z"), "26 Oct 15:00");
+    }
 }
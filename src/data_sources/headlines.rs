@@ -0,0 +1,244 @@
+use super::DataSource;
+use crate::config::deserialize_duration;
+use crate::time::Localization;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+fn default_count() -> usize {
+    8
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeadlinesConfig {
+    pub feeds: Vec<HeadlineFeed>,
+    /// How many of the latest headlines to show.
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// How long to wait for each feed, e.g. `10s`. Feeds that take longer are left out.
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeadlineFeed {
+    /// RSS 2.0 or Atom feed.
+    pub url: String,
+    /// Name to show, defaults to the feed's own title.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Headline {
+    pub title: String,
+    /// Name of the feed it came from.
+    pub source: String,
+    pub published: Option<DateTime<Utc>>,
+}
+
+impl Headline {
+    /// How long ago it was published, e.g. `25m`, `3h` or `2d`.
+    #[must_use]
+    pub fn age(&self) -> Option<String> {
+        self.age_at(Utc::now())
+    }
+
+    fn age_at(&self, now: DateTime<Utc>) -> Option<String> {
+        let minutes = (now - self.published?).num_minutes().max(0);
+        Some(match minutes {
+            0..60 => format!("{minutes}m"),
+            60..1440 => format!("{}h", minutes / 60),
+            _ => format!("{}d", minutes / 1440),
+        })
+    }
+}
+
+/// Latest headlines from RSS and Atom feeds.
+pub struct HeadlinesSource {
+    config: HeadlinesConfig,
+}
+
+impl DataSource for HeadlinesSource {
+    const NAME: &'static str = "headlines";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+    type Config = HeadlinesConfig;
+    type Output = Vec<Headline>;
+
+    fn new(config: Self::Config, _localization: &Localization) -> anyhow::Result<Self> {
+        if config.feeds.is_empty() {
+            anyhow::bail!("at least one feed must be configured");
+        }
+        if config.count == 0 {
+            anyhow::bail!("count must be at least 1");
+        }
+        for feed in &config.feeds {
+            Url::parse(&feed.url).map_err(|e| anyhow::anyhow!("feed url '{}' {e}", feed.url))?;
+        }
+        Ok(Self { config })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        // all at once, so the slowest feed sets how long this takes rather than the sum
+        let mut tasks = JoinSet::new();
+        for feed in &self.config.feeds {
+            let client = client.clone();
+            let feed = feed.clone();
+            let timeout = self.config.timeout;
+            tasks.spawn(async move {
+                let headlines = fetch_feed(&client, &feed, timeout).await;
+                (feed.url, headlines)
+            });
+        }
+
+        let mut headlines = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(res) = tasks.join_next().await {
+            match res? {
+                (_, Ok(feed_headlines)) => headlines.extend(feed_headlines),
+                (url, Err(e)) => {
+                    tracing::warn!("Leaving out feed {url}: {e}");
+                    errors.push(format!("{url}: {e}"));
+                }
+            }
+        }
+        if errors.len() == self.config.feeds.len() {
+            anyhow::bail!("every feed failed: {}", errors.join(", "));
+        }
+
+        Ok(latest(headlines, self.config.count))
+    }
+}
+
+async fn fetch_feed(
+    client: &Client,
+    feed: &HeadlineFeed,
+    timeout: Duration,
+) -> anyhow::Result<Vec<Headline>> {
+    let body = client
+        .get(&feed.url)
+        .timeout(timeout)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let parsed = feed_rs::parser::parse(body.as_ref())?;
+
+    let source = feed
+        .name
+        .clone()
+        .or_else(|| parsed.title.map(|t| t.content.trim().to_string()))
+        .unwrap_or_else(|| feed.url.clone());
+    Ok(parsed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let title = entry.title?.content;
+            // titles often come with stray newlines and indentation from the XML
+            let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!title.is_empty()).then(|| Headline {
+                title,
+                source: source.clone(),
+                published: entry.published.or(entry.updated),
+            })
+        })
+        .collect())
+}
+
+/// The newest `count` headlines, with stories carried by several feeds shown once.
+fn latest(mut headlines: Vec<Headline>, count: usize) -> Vec<Headline> {
+    // undated ones last
+    headlines.sort_by_key(|h| std::cmp::Reverse(h.published));
+    let mut seen = HashSet::new();
+    headlines
+        .into_iter()
+        .filter(|h| seen.insert(h.title.to_lowercase()))
+        .take(count)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::fixture_server;
+    use axum::routing::get;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Local News</title>
+  <item><title>Bridge reopens after repairs</title><pubDate>Thu, 10 Jul 2025 08:00:00 GMT</pubDate></item>
+  <item><title>
+    Library extends opening hours
+  </title><pubDate>Wed, 09 Jul 2025 18:30:00 GMT</pubDate></item>
+</channel></rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>City Wire</title>
+  <id>urn:city-wire</id>
+  <updated>2025-07-10T09:00:00Z</updated>
+  <entry><id>1</id><title>Bridge Reopens After Repairs</title><updated>2025-07-10T07:45:00Z</updated></entry>
+  <entry><id>2</id><title>Heatwave warning for the weekend</title><updated>2025-07-10T09:00:00Z</updated></entry>
+</feed>"#;
+
+    async fn stand_in() -> String {
+        let app = axum::Router::new()
+            .route("/rss", get(|| async { RSS }))
+            .route("/atom", get(|| async { ATOM }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    RSS
+                }),
+            );
+        fixture_server(app).await
+    }
+
+    #[tokio::test]
+    async fn merges_feeds_and_skips_slow_ones() {
+        let base = stand_in().await;
+        let config: HeadlinesConfig = toml::from_str(&format!(
+            r#"
+            count = 3
+            timeout = "1s"
+            feeds = [
+                {{ url = "{base}/rss" }},
+                {{ url = "{base}/atom", name = "Wire" }},
+                {{ url = "{base}/slow" }},
+            ]
+            "#
+        ))
+        .unwrap();
+        let source = HeadlinesSource::new(config, &Localization::default()).unwrap();
+
+        let headlines = source.fetch(&Client::new()).await.unwrap();
+        let titles: Vec<_> = headlines
+            .iter()
+            .map(|h| (h.title.as_str(), h.source.as_str()))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                ("Heatwave warning for the weekend", "Wire"),
+                ("Bridge reopens after repairs", "Local News"),
+                ("Library extends opening hours", "Local News"),
+            ]
+        );
+
+        let now = "2025-07-10T10:30:00Z".parse().unwrap();
+        let ages: Vec<_> = headlines.iter().filter_map(|h| h.age_at(now)).collect();
+        assert_eq!(ages, ["1h", "2h", "16h"]);
+    }
+}
@@ -1,7 +1,361 @@
+mod air_quality;
+mod arrivals;
+mod bikes;
+mod cache;
+mod calendar;
+mod closures;
 mod football;
+mod headlines;
+mod rail;
+mod standings;
 mod tube;
 mod weather;
 
-pub use football::{Match, fetch_arsenal_matches};
-pub use tube::{LineStatus, fetch_tube_status};
-pub use weather::{DayForecast, Weather, fetch_weather};
+pub use air_quality::{AirQuality, AirQualityConfig, AirQualitySource, Band, Reading};
+pub use arrivals::{ArrivalsConfig, ArrivalsSource, Departure, StopConfig, StopDepartures};
+pub use bikes::{BikesConfig, BikesSource, DockConfig, DockStatus};
+pub use cache::Fetched;
+pub use calendar::{Agenda, AgendaDay, AgendaEvent, CalendarConfig, CalendarSource, FeedConfig};
+pub use closures::{ClosuresSource, WeekendClosures, weekend_is_near};
+pub use football::{Fixtures, FootballConfig, FootballSource, Match, MatchStatus, TeamConfig};
+pub use headlines::{Headline, HeadlineFeed, HeadlinesConfig, HeadlinesSource};
+pub use rail::{RailConfig, RailDepartures, RailService, RailSource};
+pub use standings::{StandingRow, Standings, StandingsConfig, StandingsSource};
+pub use tube::{LineStatus, Severity, StatusDetail, TubeConfig, TubeSource};
+pub use weather::{
+    Condition, DayForecast, HourForecast, ProviderConfig, ProviderKind, Units, Weather,
+    WeatherConfig, WeatherSource,
+};
+
+use crate::config::parse_duration;
+use crate::time::Localization;
+use cache::SourceCache;
+use reqwest::Client;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use toml::Table;
+
+/// A source of data for the pages, e.g. the tube status.
+///
+/// Each source is configured by the section of the config file named after it, and is disabled
+/// when that section is missing.
+pub trait DataSource: Sized + Send + Sync + 'static {
+    /// Stable name of the source, also used as its config section.
+    const NAME: &'static str;
+    /// How often the data should be refreshed, unless overridden by `refresh` in the config.
+    const REFRESH_INTERVAL: Duration;
+
+    type Config: DeserializeOwned;
+    /// Serialisable so runtime templates can use it too.
+    type Output: Clone + Serialize + Send + Sync + 'static;
+
+    /// Creates the source from its config section, rejecting invalid settings. Dates and times
+    /// in the output should be shown in `localization`.
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self>;
+
+    fn fetch(&self, client: &Client) -> impl Future<Output = anyhow::Result<Self::Output>> + Send;
+}
+
+type AnyOutput = Arc<dyn Any + Send + Sync>;
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Object safe version of [`DataSource`] so different sources can live in the registry.
+trait ErasedSource: Send + Sync {
+    fn fetch<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, anyhow::Result<AnyOutput>>;
+
+    /// Serialises an output this source fetched.
+    fn to_json(&self, output: &AnyOutput) -> serde_json::Value;
+}
+
+impl<S: DataSource> ErasedSource for S {
+    fn fetch<'a>(&'a self, client: &'a Client) -> BoxFuture<'a, anyhow::Result<AnyOutput>> {
+        Box::pin(async move {
+            let output = DataSource::fetch(self, client).await?;
+            Ok(Arc::new(output) as AnyOutput)
+        })
+    }
+
+    fn to_json(&self, output: &AnyOutput) -> serde_json::Value {
+        let output = output
+            .downcast_ref::<S::Output>()
+            .expect("sources only see their own outputs");
+        serde_json::to_value(output).unwrap_or_else(|e| {
+            tracing::error!("Couldn't serialise {}: {e}", S::NAME);
+            serde_json::Value::Null
+        })
+    }
+}
+
+/// The data sources enabled in the config, looked up by name.
+#[derive(Default)]
+pub struct Registry {
+    sources: HashMap<&'static str, Arc<SourceCache>>,
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.sources.keys()).finish()
+    }
+}
+
+impl Registry {
+    /// Builds the registry from the config sections, one per source. Every problem is added to
+    /// `problems` rather than stopping at the first.
+    pub fn from_sections(
+        sections: &Table,
+        localization: Localization,
+        problems: &mut Vec<String>,
+    ) -> Self {
+        let mut sections = sections.clone();
+        tube::share_settings(&mut sections, &[ClosuresSource::NAME]);
+        tube::share_api_key(&mut sections, &[ArrivalsSource::NAME, BikesSource::NAME]);
+        football::share_api_key(&mut sections, &[StandingsSource::NAME]);
+        weather::share_location(&mut sections, &[AirQualitySource::NAME]);
+
+        let mut builder = RegistryBuilder {
+            sections: &sections,
+            localization,
+            problems,
+            registry: Self::default(),
+            known: Vec::new(),
+        };
+        builder.register::<TubeSource>();
+        builder.register::<ClosuresSource>();
+        builder.register::<WeatherSource>();
+        builder.register::<AirQualitySource>();
+        builder.register::<FootballSource>();
+        builder.register::<StandingsSource>();
+        builder.register::<ArrivalsSource>();
+        builder.register::<RailSource>();
+        builder.register::<BikesSource>();
+        builder.register::<CalendarSource>();
+        builder.register::<HeadlinesSource>();
+        builder.finish()
+    }
+
+    /// Whether the source is configured.
+    #[must_use]
+    pub fn is_enabled<S: DataSource>(&self) -> bool {
+        self.sources.contains_key(S::NAME)
+    }
+
+    /// Names of the enabled sources.
+    #[must_use]
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sources.keys().copied().collect()
+    }
+
+    #[must_use]
+    pub fn refresh_interval(&self, name: &str) -> Option<Duration> {
+        self.sources.get(name).map(|s| s.ttl())
+    }
+
+    /// Keeps every source's cache warm by refreshing it in the background at its interval.
+    pub fn spawn_refresh(&self, client: &Client) {
+        for (name, cache) in &self.sources {
+            tracing::debug!("Refreshing {name} every {:?}", cache.ttl());
+            let cache = cache.clone();
+            let client = client.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(cache.ttl());
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    cache.refresh(&client).await;
+                }
+            });
+        }
+    }
+
+    /// Fetches a source now regardless of its cache, for when a fresher value than its refresh
+    /// interval allows is needed.
+    pub async fn refresh<S: DataSource>(&self, client: &Client) {
+        if let Some(cache) = self.sources.get(S::NAME) {
+            cache.refresh(client).await;
+        }
+    }
+
+    /// Gets the named sources concurrently, from the cache where it is still fresh. Names of
+    /// disabled sources are skipped.
+    pub async fn fetch(&self, client: &Client, names: &[&'static str]) -> SourceData {
+        let mut tasks = JoinSet::new();
+        for &name in names {
+            let Some(cache) = self.sources.get(name) else {
+                continue;
+            };
+            let cache = cache.clone();
+            let client = client.clone();
+            tasks.spawn(async move {
+                let value = cache.get(&client).await;
+                (name, value, cache)
+            });
+        }
+
+        let mut data = SourceData::default();
+        while let Some(res) = tasks.join_next().await {
+            match res {
+                Ok((name, value, cache)) => {
+                    data.values.insert(name, value);
+                    data.caches.insert(name, cache);
+                }
+                Err(e) => tracing::error!("Data source task failed: {e}"),
+            }
+        }
+
+        data
+    }
+}
+
+struct RegistryBuilder<'a> {
+    sections: &'a Table,
+    localization: Localization,
+    problems: &'a mut Vec<String>,
+    registry: Registry,
+    known: Vec<&'static str>,
+}
+
+impl RegistryBuilder<'_> {
+    fn register<S: DataSource>(&mut self) {
+        self.known.push(S::NAME);
+
+        let Some(section) = self.sections.get(S::NAME) else {
+            tracing::info!("No [{}] section configured, it is disabled", S::NAME);
+            return;
+        };
+        let Some(section) = section.as_table() else {
+            self.problems.push(format!("{}: expected a table", S::NAME));
+            return;
+        };
+
+        let mut section = section.clone();
+        let refresh_interval = match section.remove("refresh") {
+            None => S::REFRESH_INTERVAL,
+            Some(value) => match value.as_str().map(parse_duration) {
+                Some(Ok(interval)) => interval,
+                Some(Err(e)) => {
+                    self.problems.push(format!("{}.refresh: {e}", S::NAME));
+                    S::REFRESH_INTERVAL
+                }
+                None => {
+                    self.problems.push(format!(
+                        "{}.refresh: expected a duration like '5m'",
+                        S::NAME
+                    ));
+                    S::REFRESH_INTERVAL
+                }
+            },
+        };
+
+        let config: S::Config = match section.try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                self.problems.push(format!("{}: {}", S::NAME, e.message()));
+                return;
+            }
+        };
+
+        match S::new(config, &self.localization) {
+            Ok(source) => {
+                self.registry.sources.insert(
+                    S::NAME,
+                    Arc::new(SourceCache::new(
+                        S::NAME,
+                        Arc::new(source),
+                        refresh_interval,
+                        self.localization.tz,
+                    )),
+                );
+            }
+            Err(e) => self.problems.push(format!("{}: {e}", S::NAME)),
+        }
+    }
+
+    fn finish(self) -> Registry {
+        for name in self.sections.keys() {
+            if !self.known.contains(&name.as_str()) {
+                self.problems.push(format!("Unknown section '[{name}]'"));
+            }
+        }
+        self.registry
+    }
+}
+
+/// The results of fetching a set of sources for a page.
+#[derive(Default)]
+pub struct SourceData {
+    values: HashMap<&'static str, Result<Fetched<AnyOutput>, String>>,
+    /// Where each value came from, for serialising it.
+    caches: HashMap<&'static str, Arc<SourceCache>>,
+}
+
+impl SourceData {
+    /// Returns `None` if the source is disabled or wasn't requested, and an error if it has
+    /// never been fetched successfully.
+    #[must_use]
+    pub fn get<S: DataSource>(&self) -> Option<Result<Fetched<&S::Output>, &str>> {
+        self.values.get(S::NAME).map(|value| match value {
+            Ok(fetched) => Ok(fetched.as_ref().map(|value| {
+                value
+                    .downcast_ref::<S::Output>()
+                    .expect("sources are stored under their own name")
+            })),
+            Err(e) => Err(e.as_str()),
+        })
+    }
+
+    /// Whether the source was requested and is enabled, even if it has no data yet.
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Like [`SourceData::get`], but owning the value, for handing to templates.
+    #[must_use]
+    pub fn get_cloned<S: DataSource>(&self) -> Option<Result<Fetched<S::Output>, String>> {
+        self.get::<S>().map(|value| {
+            value
+                .map(|fetched| fetched.map(Clone::clone))
+                .map_err(ToString::to_string)
+        })
+    }
+
+    /// Every value as JSON keyed by source name, for runtime templates. Each is an object with
+    /// the `value`, `fetched_at`, `stale_since` and `stale_label`, or just an `error` when the
+    /// source has never been fetched successfully.
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        self.values
+            .iter()
+            .map(|(&name, value)| {
+                let entry = match (value, self.caches.get(name)) {
+                    (Ok(fetched), Some(cache)) => serde_json::json!({
+                        "value": cache.to_json(&fetched.value),
+                        "fetched_at": fetched.fetched_at,
+                        "stale_since": fetched.stale_since,
+                        "stale_label": fetched.stale_label(),
+                    }),
+                    (Err(e), _) => serde_json::json!({ "error": e }),
+                    (Ok(_), None) => serde_json::json!({ "error": "No data yet" }),
+                };
+                (name.to_string(), entry)
+            })
+            .collect()
+    }
+}
+
+/// Serves `app` on a free local port and returns its base url, so sources can be tested against
+/// recorded responses instead of the real APIs.
+#[cfg(test)]
+async fn fixture_server(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
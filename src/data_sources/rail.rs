@@ -0,0 +1,270 @@
+use super::DataSource;
+use crate::time::Localization;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_base_url() -> String {
+    "https://huxley2.azurewebsites.net".to_string()
+}
+
+fn default_rows() -> usize {
+    6
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RailConfig {
+    /// Root of a Huxley-style JSON proxy for the Darwin departure boards.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Darwin access token, if the proxy needs one.
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// CRS code of the station to show departures from, e.g. `PAD`.
+    pub crs: String,
+    /// Only show trains calling at this station.
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default = "default_rows")]
+    pub rows: usize,
+}
+
+/// Departures board for a National Rail station.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RailDepartures {
+    pub station: String,
+    pub services: Vec<RailService>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RailService {
+    /// Scheduled departure, e.g. `14:05`.
+    pub scheduled: String,
+    /// `On time`, `Delayed`, `Cancelled` or the expected time.
+    pub expected: String,
+    pub platform: Option<String>,
+    pub destination: String,
+    pub cancelled: bool,
+    /// Why the train is cancelled or delayed.
+    pub reason: Option<String>,
+}
+
+impl RailService {
+    #[must_use]
+    pub fn is_on_time(&self) -> bool {
+        self.expected == "On time" || self.expected == self.scheduled
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuxleyBoard {
+    location_name: String,
+    #[serde(default)]
+    train_services: Option<Vec<HuxleyService>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuxleyService {
+    std: String,
+    etd: String,
+    #[serde(default)]
+    platform: Option<String>,
+    destination: Vec<HuxleyLocation>,
+    #[serde(default)]
+    is_cancelled: bool,
+    #[serde(default)]
+    cancel_reason: Option<String>,
+    #[serde(default)]
+    delay_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HuxleyLocation {
+    location_name: String,
+}
+
+/// National Rail departures from a Huxley-style proxy for Darwin.
+pub struct RailSource {
+    config: RailConfig,
+}
+
+impl DataSource for RailSource {
+    const NAME: &'static str = "rail";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(2 * 60);
+
+    type Config = RailConfig;
+    type Output = RailDepartures;
+
+    fn new(config: Self::Config, _localization: &Localization) -> anyhow::Result<Self> {
+        let is_crs = |code: &str| code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic());
+        if !is_crs(&config.crs) {
+            anyhow::bail!("crs must be a 3 letter station code, got '{}'", config.crs);
+        }
+        if let Some(to) = config.to.as_deref().filter(|to| !is_crs(to)) {
+            anyhow::bail!("to must be a 3 letter station code, got '{to}'");
+        }
+        if config.rows == 0 {
+            anyhow::bail!("rows must be at least 1");
+        }
+        Url::parse(&config.base_url)
+            .map_err(|e| anyhow::anyhow!("base_url '{}' {e}", config.base_url))?;
+        Ok(Self { config })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        fetch_departures(client, &self.config).await
+    }
+}
+
+async fn fetch_departures(
+    client: &Client,
+    config: &RailConfig,
+) -> Result<RailDepartures, anyhow::Error> {
+    let crs = config.crs.to_uppercase();
+    let path = match &config.to {
+        Some(to) => format!("departures/{crs}/to/{}/{}", to.to_uppercase(), config.rows),
+        None => format!("departures/{crs}/{}", config.rows),
+    };
+    let mut url = Url::parse(&format!("{}/{path}", config.base_url.trim_end_matches('/')))?;
+    if let Some(token) = &config.access_token {
+        url.query_pairs_mut().append_pair("accessToken", token);
+    }
+
+    let board: HuxleyBoard = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(RailDepartures {
+        station: board.location_name,
+        services: board
+            .train_services
+            .unwrap_or_default()
+            .into_iter()
+            .map(|s| RailService {
+                destination: s
+                    .destination
+                    .into_iter()
+                    .map(|d| d.location_name)
+                    .collect::<Vec<_>>()
+                    .join(" & "),
+                cancelled: s.is_cancelled || s.etd == "Cancelled",
+                reason: if s.is_cancelled {
+                    s.cancel_reason
+                } else {
+                    s.delay_reason
+                },
+                scheduled: s.std,
+                expected: s.etd,
+                platform: s.platform,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::fixture_server;
+    use axum::Json;
+    use axum::extract::Path;
+    use axum::routing::get;
+
+    /// Serves a recorded board, so the source can be tested without the real proxy.
+    async fn stand_in() -> String {
+        async fn departures(Path((crs, rows)): Path<(String, usize)>) -> Json<serde_json::Value> {
+            assert_eq!((crs.as_str(), rows), ("PAD", 3));
+            Json(serde_json::json!({
+                "locationName": "London Paddington",
+                "crs": "PAD",
+                "trainServices": [
+                    {
+                        "std": "14:05", "etd": "On time", "platform": "4",
+                        "destination": [{ "locationName": "Reading", "crs": "RDG" }],
+                        "isCancelled": false, "cancelReason": null, "delayReason": null
+                    },
+                    {
+                        "std": "14:12", "etd": "14:20", "platform": null,
+                        "destination": [{ "locationName": "Oxford", "crs": "OXF" }],
+                        "isCancelled": false, "delayReason": "This train has been delayed by a signalling fault"
+                    },
+                    {
+                        "std": "14:15", "etd": "Cancelled",
+                        "destination": [
+                            { "locationName": "Bristol Temple Meads", "crs": "BRI" },
+                            { "locationName": "Cardiff Central", "crs": "CDF" }
+                        ],
+                        "isCancelled": true, "cancelReason": "This train has been cancelled because of a shortage of train crew"
+                    }
+                ]
+            }))
+        }
+
+        let app = axum::Router::new().route("/departures/{crs}/{rows}", get(departures));
+        fixture_server(app).await
+    }
+
+    #[tokio::test]
+    async fn reads_departure_board() {
+        let config: RailConfig = toml::from_str(&format!(
+            r#"
+            base_url = "{}"
+            crs = "pad"
+            rows = 3
+            "#,
+            stand_in().await
+        ))
+        .unwrap();
+        let source = RailSource::new(config, &Localization::default()).unwrap();
+
+        let board = source.fetch(&Client::new()).await.unwrap();
+        assert_eq!(board.station, "London Paddington");
+
+        let services: Vec<_> = board
+            .services
+            .iter()
+            .map(|s| {
+                (
+                    s.scheduled.as_str(),
+                    s.is_on_time(),
+                    s.cancelled,
+                    s.platform.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            services,
+            [
+                ("14:05", true, false, Some("4")),
+                ("14:12", false, false, None),
+                ("14:15", false, true, None),
+            ]
+        );
+        assert_eq!(
+            board.services[2].destination,
+            "Bristol Temple Meads & Cardiff Central"
+        );
+        assert!(board.services[2].reason.as_ref().unwrap().contains("crew"));
+    }
+
+    #[test]
+    fn rejects_bad_station_codes() {
+        let config = |crs: &str| RailConfig {
+            base_url: default_base_url(),
+            access_token: None,
+            crs: crs.to_string(),
+            to: None,
+            rows: 5,
+        };
+        let localization = Localization::default();
+        assert!(RailSource::new(config("PAD"), &localization).is_ok());
+        assert!(RailSource::new(config("Paddington"), &localization).is_err());
+    }
+}
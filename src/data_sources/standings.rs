@@ -0,0 +1,215 @@
+use super::DataSource;
+use super::football::football_data_get;
+use crate::time::Localization;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_rows() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StandingsConfig {
+    pub api_key: String,
+    /// Competition code, e.g. `PL`.
+    pub competition: String,
+    /// Team to highlight and centre the table on.
+    pub team_id: u32,
+    /// How many rows of the table to show.
+    #[serde(default = "default_rows")]
+    pub rows: usize,
+}
+
+/// The part of a league table around the followed team.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Standings {
+    pub competition: String,
+    pub rows: Vec<StandingRow>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StandingRow {
+    pub position: u32,
+    pub team: String,
+    pub crest_url: String,
+    pub played: u32,
+    pub goal_difference: i32,
+    pub points: u32,
+    /// Most recent results, oldest first, as `W`, `D` or `L`.
+    pub form: Vec<String>,
+    /// Whether this is the followed team.
+    pub highlighted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct StandingsResponse {
+    competition: StandingsCompetition,
+    standings: Vec<StandingsTable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StandingsCompetition {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StandingsTable {
+    #[serde(rename = "type")]
+    kind: String,
+    table: Vec<StandingsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StandingsEntry {
+    position: u32,
+    team: StandingsTeam,
+    #[serde(rename = "playedGames")]
+    played_games: u32,
+    form: Option<String>,
+    points: u32,
+    #[serde(rename = "goalDifference")]
+    goal_difference: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct StandingsTeam {
+    id: u32,
+    #[serde(rename = "shortName")]
+    short_name: String,
+    crest: String,
+}
+
+/// Current league table for a competition from football-data.org.
+pub struct StandingsSource {
+    config: StandingsConfig,
+}
+
+impl DataSource for StandingsSource {
+    const NAME: &'static str = "football_standings";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    type Config = StandingsConfig;
+    type Output = Standings;
+
+    fn new(config: Self::Config, _localization: &Localization) -> anyhow::Result<Self> {
+        if config.api_key.trim().is_empty() {
+            anyhow::bail!("api_key must not be empty");
+        }
+        if config.rows == 0 {
+            anyhow::bail!("rows must be at least 1");
+        }
+        Ok(Self { config })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        fetch_standings(client, &self.config).await
+    }
+}
+
+async fn fetch_standings(
+    client: &Client,
+    config: &StandingsConfig,
+) -> Result<Standings, anyhow::Error> {
+    let response: StandingsResponse = football_data_get(
+        client,
+        &config.api_key,
+        &format!("competitions/{}/standings", config.competition),
+    )
+    .await?;
+
+    let table = response
+        .standings
+        .into_iter()
+        .find(|s| s.kind == "TOTAL")
+        .ok_or_else(|| anyhow::anyhow!("No overall table for {}", config.competition))?
+        .table;
+    if !table.iter().any(|e| e.team.id == config.team_id) {
+        tracing::warn!(
+            "Team {} isn't in the {} table, showing the top of it",
+            config.team_id,
+            config.competition
+        );
+    }
+
+    Ok(Standings {
+        competition: response.competition.name,
+        rows: trim_around(table, config.team_id, config.rows),
+    })
+}
+
+/// Keeps `rows` rows of the table with the followed team as close to the middle as possible.
+fn trim_around(table: Vec<StandingsEntry>, team_id: u32, rows: usize) -> Vec<StandingRow> {
+    let team_idx = table.iter().position(|e| e.team.id == team_id).unwrap_or(0);
+    let start = team_idx
+        .saturating_sub(rows / 2)
+        .min(table.len().saturating_sub(rows));
+
+    table
+        .into_iter()
+        .skip(start)
+        .take(rows)
+        .map(|e| StandingRow {
+            position: e.position,
+            highlighted: e.team.id == team_id,
+            team: e.team.short_name,
+            crest_url: e.team.crest,
+            played: e.played_games,
+            goal_difference: e.goal_difference,
+            points: e.points,
+            form: e
+                .form
+                .unwrap_or_default()
+                .split(',')
+                .filter(|r| !r.is_empty())
+                .rev()
+                .map(ToString::to_string)
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(len: u32) -> Vec<StandingsEntry> {
+        (1..=len)
+            .map(|i| StandingsEntry {
+                position: i,
+                team: StandingsTeam {
+                    id: i * 10,
+                    short_name: format!("Team {i}"),
+                    crest: String::new(),
+                },
+                played_games: 20,
+                form: Some("W,D,L".to_string()),
+                points: 60 - i,
+                goal_difference: 0,
+            })
+            .collect()
+    }
+
+    fn positions(rows: &[StandingRow]) -> Vec<u32> {
+        rows.iter().map(|r| r.position).collect()
+    }
+
+    #[test]
+    fn centres_on_team() {
+        let rows = trim_around(table(20), 100, 5);
+        assert_eq!(positions(&rows), [8, 9, 10, 11, 12]);
+        assert!(rows[2].highlighted);
+        assert_eq!(rows[2].form, ["L", "D", "W"]);
+    }
+
+    #[test]
+    fn clamps_to_ends_of_table() {
+        assert_eq!(positions(&trim_around(table(20), 10, 5)), [1, 2, 3, 4, 5]);
+        assert_eq!(
+            positions(&trim_around(table(20), 200, 5)),
+            [16, 17, 18, 19, 20]
+        );
+        assert_eq!(positions(&trim_around(table(3), 20, 5)), [1, 2, 3]);
+    }
+}
@@ -1,52 +1,382 @@
-use reqwest::Client;
-use serde::Deserialize;
+use super::DataSource;
+use crate::time::Localization;
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use toml::{Table, Value};
 
-use crate::SERVER_CONFIG;
+const API_BASE: &str = "https://api.tfl.gov.uk";
 
-#[derive(Debug, Clone, Default)]
+/// TfL modes that report line statuses.
+const MODES: &[&str] = &["tube", "overground", "dlr", "elizabeth-line", "tram"];
+
+fn default_modes() -> Vec<String> {
+    vec!["tube".to_string()]
+}
+
+fn default_reason_length() -> usize {
+    120
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TubeConfig {
+    pub api_key: String,
+    /// Which of `tube`, `overground`, `dlr`, `elizabeth-line` and `tram` to show.
+    #[serde(default = "default_modes")]
+    pub modes: Vec<String>,
+    /// Line names or ids to show, e.g. `["Victoria", "elizabeth"]`. Empty means every line.
+    #[serde(default)]
+    pub lines: Vec<String>,
+    /// Disruption reasons are cut to this many characters to fit the panel. 0 hides them.
+    #[serde(default = "default_reason_length")]
+    pub reason_length: usize,
+}
+
+impl TubeConfig {
+    pub(super) fn wants(&self, line: &TflLineResponse) -> bool {
+        self.lines.is_empty()
+            || self
+                .lines
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(&line.id) || l.eq_ignore_ascii_case(&line.name))
+    }
+}
+
+/// How bad a status is, worst first so lines sort with the worst at the top.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Closed,
+    Severe,
+    Minor,
+    Information,
+    #[default]
+    Good,
+}
+
+impl Severity {
+    /// Maps TfL's `statusSeverity` codes, which aren't ordered by how bad they are.
+    fn from_tfl(code: u8) -> Self {
+        match code {
+            1..=5 | 16 | 20 => Self::Closed,
+            6..=8 | 11 => Self::Severe,
+            9 | 12..=15 | 17 => Self::Minor,
+            10 | 18 => Self::Good,
+            _ => Self::Information,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct LineStatus {
+    pub id: String,
     pub name: String,
-    pub status: String,
+    /// Every status currently reported for the line, worst first.
+    pub statuses: Vec<StatusDetail>,
+}
+
+impl LineStatus {
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        self.statuses.first().map_or(Severity::Good, |s| s.severity)
+    }
+
+    #[must_use]
+    pub fn is_good_service(&self) -> bool {
+        self.severity() == Severity::Good
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusDetail {
+    pub description: String,
+    pub severity: Severity,
+    /// Why the line is disrupted, already truncated to fit.
     pub reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct TflLineResponse {
+pub(super) struct TflLineResponse {
+    pub(super) id: String,
     name: String,
-    #[serde(rename = "lineStatuses")]
+    #[serde(default, rename = "lineStatuses")]
     line_statuses: Vec<TflLineStatus>,
 }
 
 #[derive(Debug, Deserialize)]
 struct TflLineStatus {
+    #[serde(rename = "statusSeverity")]
+    status_severity: u8,
     #[serde(rename = "statusSeverityDescription")]
     status_severity_description: String,
     reason: Option<String>,
 }
 
-pub async fn fetch_tube_status(client: &Client) -> Result<Vec<LineStatus>, anyhow::Error> {
-    let url = format!(
-        "https://api.tfl.gov.uk/Line/Mode/tube/Status?app_key={}",
-        SERVER_CONFIG.tube_api_key
-    );
-    let response: Vec<TflLineResponse> = client.get(&url).send().await?.json().await?;
+/// Live status of the TfL lines we use.
+pub struct TubeSource {
+    config: TubeConfig,
+}
+
+impl DataSource for TubeSource {
+    const NAME: &'static str = "tube";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
-    let lines = response
+    type Config = TubeConfig;
+    type Output = Vec<LineStatus>;
+
+    fn new(config: Self::Config, _localization: &Localization) -> anyhow::Result<Self> {
+        if config.api_key.trim().is_empty() {
+            anyhow::bail!("api_key must not be empty");
+        }
+        if config.modes.is_empty() {
+            anyhow::bail!("at least one mode must be configured");
+        }
+        if let Some(mode) = config.modes.iter().find(|m| !MODES.contains(&m.as_str())) {
+            anyhow::bail!("unknown mode '{mode}', expected one of {MODES:?}");
+        }
+        Ok(Self { config })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        fetch_tube_status(client, &self.config).await
+    }
+}
+
+/// Lets sections that follow the same lines, like `[tube_closures]`, leave out `api_key`,
+/// `modes` and `lines` and use the ones from `[tube]`, so they're only listed once.
+pub(super) fn share_settings(sections: &mut Table, line_sections: &[&str]) {
+    let Some(tube) = sections
+        .get(TubeSource::NAME)
+        .and_then(Value::as_table)
+        .cloned()
+    else {
+        return;
+    };
+    for name in line_sections {
+        if let Some(section) = sections.get_mut(*name).and_then(Value::as_table_mut) {
+            for key in ["api_key", "modes", "lines"] {
+                if let Some(value) = tube.get(key) {
+                    section.entry(key).or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Lets the other TfL sections leave out `api_key` and use the one from `[tube]`, which is also
+/// where `TUBE_API_KEY` goes.
+pub(super) fn share_api_key(sections: &mut Table, tfl_sections: &[&str]) {
+    let Some(key) = sections
+        .get(TubeSource::NAME)
+        .and_then(|tube| tube.get("api_key"))
+        .cloned()
+    else {
+        return;
+    };
+    for name in tfl_sections {
+        if let Some(section) = sections.get_mut(*name).and_then(Value::as_table_mut) {
+            section.entry("api_key").or_insert_with(|| key.clone());
+        }
+    }
+}
+
+/// Makes a request to the TfL unified API with our key, `path` being relative to the API root.
+pub(super) async fn tfl_get<T: DeserializeOwned>(
+    client: &Client,
+    api_key: &str,
+    path: &str,
+) -> Result<T, anyhow::Error> {
+    let url = Url::parse_with_params(&format!("{API_BASE}/{path}"), [("app_key", api_key)])?;
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn fetch_tube_status(
+    client: &Client,
+    config: &TubeConfig,
+) -> Result<Vec<LineStatus>, anyhow::Error> {
+    let path = format!("Line/Mode/{}/Status", config.modes.join(","));
+    let response: Vec<TflLineResponse> = tfl_get(client, &config.api_key, &path).await?;
+
+    Ok(to_line_statuses(config, response))
+}
+
+/// Keeps the configured lines, worst affected first, each with all of its statuses.
+pub(super) fn to_line_statuses(
+    config: &TubeConfig,
+    response: Vec<TflLineResponse>,
+) -> Vec<LineStatus> {
+    let mut lines: Vec<LineStatus> = response
         .into_iter()
+        .filter(|line| config.wants(line))
         .map(|line| {
-            let status = line.line_statuses.first().map_or_else(
-                || "Unknown".to_string(),
-                |s| s.status_severity_description.clone(),
-            );
-            let reason = line.line_statuses.first().and_then(|s| s.reason.clone());
+            let mut statuses: Vec<StatusDetail> = Vec::new();
+            for s in line.line_statuses {
+                let severity = Severity::from_tfl(s.status_severity);
+                let reason = s
+                    .reason
+                    .filter(|_| severity != Severity::Good && config.reason_length > 0)
+                    .map(|r| truncate(strip_line_prefix(&r, &line.name), config.reason_length));
+                // TfL repeats a status once per period it applies to
+                if !statuses
+                    .iter()
+                    .any(|d| d.description == s.status_severity_description && d.reason == reason)
+                {
+                    statuses.push(StatusDetail {
+                        description: s.status_severity_description,
+                        severity,
+                        reason,
+                    });
+                }
+            }
+            statuses.sort_by_key(|s| s.severity);
 
             LineStatus {
+                id: line.id,
                 name: line.name,
-                status,
-                reason,
+                statuses,
             }
         })
         .collect();
+    lines.sort_by(|a, b| a.severity().cmp(&b.severity()).then(a.name.cmp(&b.name)));
+    lines
+}
+
+/// Drops the "Central Line: " TfL puts at the start of reasons, as the line is already named.
+fn strip_line_prefix<'a>(reason: &'a str, line: &str) -> &'a str {
+    reason
+        .split_once(": ")
+        .filter(|(prefix, _)| prefix.starts_with(line))
+        .map_or(reason, |(_, rest)| rest)
+        .trim()
+}
+
+/// Cuts `text` to at most `max` characters, at a word boundary where possible.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    let cut = match cut.rfind(' ') {
+        Some(i) if i > cut.len() / 2 => &cut[..i],
+        _ => &cut,
+    };
+    format!("{}…", cut.trim_end_matches([' ', ',', '.', ';']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(lines: &[&str]) -> TubeConfig {
+        TubeConfig {
+            api_key: "key".to_string(),
+            modes: default_modes(),
+            lines: lines.iter().map(ToString::to_string).collect(),
+            reason_length: 40,
+        }
+    }
+
+    fn response() -> Vec<TflLineResponse> {
+        serde_json::from_value(serde_json::json!([
+            {
+                "id": "central", "name": "Central",
+                "lineStatuses": [{ "statusSeverity": 10, "statusSeverityDescription": "Good Service" }]
+            },
+            {
+                "id": "district", "name": "District",
+                "lineStatuses": [
+                    { "statusSeverity": 9, "statusSeverityDescription": "Minor Delays",
+                      "reason": "District Line: Minor delays due to an earlier signal failure at Earl's Court." },
+                    { "statusSeverity": 3, "statusSeverityDescription": "Part Suspended",
+                      "reason": "District Line: No service between Turnham Green and Richmond." },
+                    { "statusSeverity": 3, "statusSeverityDescription": "Part Suspended",
+                      "reason": "District Line: No service between Turnham Green and Richmond." }
+                ]
+            },
+            {
+                "id": "victoria", "name": "Victoria",
+                "lineStatuses": [{ "statusSeverity": 6, "statusSeverityDescription": "Severe Delays",
+                                   "reason": "Victoria Line: Severe delays." }]
+            }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn orders_lines_and_statuses_by_severity() {
+        let lines = to_line_statuses(&config(&[]), response());
+
+        let names: Vec<_> = lines.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["District", "Victoria", "Central"]);
+
+        let district: Vec<_> = lines[0]
+            .statuses
+            .iter()
+            .map(|s| (s.description.as_str(), s.reason.as_deref()))
+            .collect();
+        assert_eq!(
+            district,
+            [
+                (
+                    "Part Suspended",
+                    Some("No service between Turnham Green and…")
+                ),
+                (
+                    "Minor Delays",
+                    Some("Minor delays due to an earlier signal…")
+                ),
+            ]
+        );
+        assert!(lines[2].is_good_service());
+    }
+
+    #[test]
+    fn filters_to_configured_lines() {
+        let lines = to_line_statuses(&config(&["victoria", "Central"]), response());
+        let names: Vec<_> = lines.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["Victoria", "Central"]);
+    }
+
+    #[test]
+    fn maps_tfl_severity_codes() {
+        assert_eq!(Severity::from_tfl(10), Severity::Good);
+        assert_eq!(Severity::from_tfl(18), Severity::Good);
+        assert_eq!(Severity::from_tfl(9), Severity::Minor);
+        assert_eq!(Severity::from_tfl(6), Severity::Severe);
+        assert_eq!(Severity::from_tfl(20), Severity::Closed);
+        assert_eq!(Severity::from_tfl(19), Severity::Information);
+    }
+
+    #[test]
+    fn shares_settings_with_line_sections() {
+        let mut sections: Table = r#"
+            [tube]
+            api_key = "key"
+            lines = ["Victoria"]
+            [tube_closures]
+            lines = ["Jubilee"]
+            "#
+        .parse()
+        .unwrap();
+        share_settings(&mut sections, &["tube_closures"]);
+
+        let closures = &sections["tube_closures"];
+        assert_eq!(closures["api_key"].as_str(), Some("key"));
+        assert_eq!(closures["lines"][0].as_str(), Some("Jubilee"));
+        assert!(closures.get("modes").is_none());
+    }
 
-    Ok(lines)
+    #[test]
+    fn truncates_on_char_boundaries() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ééééééééééé", 5), "éééé…");
+    }
 }
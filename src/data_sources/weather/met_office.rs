@@ -0,0 +1,266 @@
+use super::{Condition, DayForecast, HourForecast, Weather, WeatherConfig, compass};
+use crate::time::Localization;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+
+pub(super) const BASE_URL: &str = "https://data.hub.api.metoffice.gov.uk";
+
+/// Site-specific forecasts come back as GeoJSON, with a single feature for the point.
+#[derive(Debug, Deserialize)]
+struct DataHubResponse<T> {
+    features: Vec<DataHubFeature<T>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataHubFeature<T> {
+    properties: DataHubProperties<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DataHubProperties<T> {
+    time_series: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DataHubHour {
+    #[serde(deserialize_with = "deserialize_time")]
+    time: DateTime<Utc>,
+    screen_temperature: f32,
+    feels_like_temperature: f32,
+    screen_relative_humidity: f32,
+    /// Metres per second.
+    wind_speed_10m: f32,
+    wind_direction_from_10m: f32,
+    #[serde(default)]
+    uv_index: f32,
+    significant_weather_code: i8,
+    #[serde(default)]
+    prob_of_precipitation: u8,
+    #[serde(default)]
+    total_precip_amount: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DataHubDay {
+    #[serde(deserialize_with = "deserialize_time")]
+    time: DateTime<Utc>,
+    // the day values are missing once the day is over
+    #[serde(default)]
+    day_significant_weather_code: Option<i8>,
+    #[serde(default)]
+    day_max_screen_temperature: Option<f32>,
+    #[serde(default)]
+    night_min_screen_temperature: Option<f32>,
+}
+
+/// DataHub gives times in UTC without seconds, e.g. `2025-01-10T22:00Z`.
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveDateTime::parse_from_str(&time, "%Y-%m-%dT%H:%MZ")
+        .map(|t| t.and_utc())
+        .map_err(serde::de::Error::custom)
+}
+
+/// Condition for a Met Office significant weather code, and whether it is a daytime one.
+fn condition(code: i8) -> (Condition, bool) {
+    // codes come in night and day pairs for the ones that look different at night
+    let is_day = !matches!(code, 0 | 2 | 9 | 13 | 16 | 19 | 22 | 25 | 28);
+    let condition = match code {
+        0..=1 => Condition::Clear,
+        2..=3 => Condition::PartlyCloudy,
+        5 => Condition::Haze,
+        6 => Condition::Fog,
+        7..=8 => Condition::Overcast,
+        -1 | 9..=10 | 12 => Condition::LightRain,
+        11 => Condition::Drizzle,
+        13..=15 => Condition::HeavyRain,
+        16..=18 => Condition::Sleet,
+        19..=21 => Condition::Hail,
+        22..=24 => Condition::LightSnow,
+        25..=27 => Condition::HeavySnow,
+        28..=30 => Condition::Thunderstorm,
+
+        _ => Condition::Unknown,
+    };
+    (condition, is_day)
+}
+
+async fn get_time_series<T: DeserializeOwned>(
+    client: &Client,
+    base_url: &str,
+    api_key: &str,
+    config: &WeatherConfig,
+    timesteps: &str,
+) -> Result<Vec<T>, anyhow::Error> {
+    let url = Url::parse_with_params(
+        &format!("{base_url}/sitespecific/v0/point/{timesteps}"),
+        [
+            ("latitude", config.lat.to_string().as_str()),
+            ("longitude", config.long.to_string().as_str()),
+            ("excludeParameterMetadata", "true"),
+        ],
+    )?;
+    let response: DataHubResponse<T> = client
+        .get(url)
+        .header("apikey", api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    response
+        .features
+        .into_iter()
+        .next()
+        .map(|feature| feature.properties.time_series)
+        .ok_or_else(|| anyhow::anyhow!("No {timesteps} forecast for this location"))
+}
+
+pub(super) async fn fetch_weather(
+    client: &Client,
+    base_url: &str,
+    api_key: &str,
+    config: &WeatherConfig,
+    localization: &Localization,
+) -> Result<Weather, anyhow::Error> {
+    let hours = get_time_series(client, base_url, api_key, config, "hourly").await?;
+    let days = get_time_series(client, base_url, api_key, config, "daily").await?;
+    to_weather(&hours, &days, config, localization, Utc::now())
+}
+
+fn to_weather(
+    hours: &[DataHubHour],
+    days: &[DataHubDay],
+    config: &WeatherConfig,
+    localization: &Localization,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Weather> {
+    let units = config.units;
+
+    // the series starts at the model run, which can be a few hours ago
+    let current = hours
+        .iter()
+        .rev()
+        .find(|h| h.time <= now)
+        .or(hours.first())
+        .ok_or_else(|| anyhow::anyhow!("No hourly forecast for this location"))?;
+
+    let hourly = hours
+        .iter()
+        .filter(|h| h.time >= current.time)
+        .take(config.hours as usize)
+        .map(|hour| HourForecast {
+            hour: localization.format(&hour.time, "%H"),
+            temperature: units.temperature(hour.screen_temperature),
+            precipitation_probability: hour.prob_of_precipitation,
+            precipitation: units.precipitation(hour.total_precip_amount),
+        })
+        .collect();
+
+    // days are midnight UTC, which is the right date in any timezone near the UK
+    let today = now.with_timezone(&localization.tz).date_naive();
+    let forecast = days
+        .iter()
+        .filter(|day| day.time.date_naive() > today)
+        .filter_map(|day| {
+            let (condition, _) = condition(day.day_significant_weather_code?);
+            Some(DayForecast {
+                day: localization.format_date(day.time.date_naive(), "%a"),
+                high: units.temperature(day.day_max_screen_temperature?).round() as i32,
+                low: units.temperature(day.night_min_screen_temperature?).round() as i32,
+                description: condition.description().to_string(),
+                icon: condition.icon(true).to_string(),
+            })
+        })
+        .take(5)
+        .collect();
+
+    let (condition, is_day) = condition(current.significant_weather_code);
+    Ok(Weather {
+        current_temp: units.temperature(current.screen_temperature).round() as i32,
+        feels_like: units.temperature(current.feels_like_temperature).round() as i32,
+        current_description: condition.description().to_string(),
+        icon: condition.icon(is_day).to_string(),
+        humidity: current.screen_relative_humidity.round() as u8,
+        wind_speed: units.speed(current.wind_speed_10m).round() as i32,
+        wind_direction: compass(current.wind_direction_from_10m),
+        uv_index: current.uv_index.round() as i32,
+        // not in the site-specific forecasts
+        sunrise: String::new(),
+        sunset: String::new(),
+        is_day,
+        units,
+        forecast,
+        hourly,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_weather_codes() {
+        assert_eq!(condition(1), (Condition::Clear, true));
+        assert_eq!(condition(0), (Condition::Clear, false));
+        assert_eq!(condition(19), (Condition::Hail, false));
+        assert_eq!(condition(21), (Condition::Hail, true));
+        assert_eq!(condition(30), (Condition::Thunderstorm, true));
+    }
+
+    #[test]
+    fn reads_hourly_and_daily_series() {
+        let hour = |time: &str, temperature: f32, code: i8| {
+            serde_json::json!({
+                "time": time, "screenTemperature": temperature, "feelsLikeTemperature": temperature - 2.0,
+                "screenRelativeHumidity": 80.6, "windSpeed10m": 5.0, "windDirectionFrom10m": 230,
+                "uvIndex": 0, "significantWeatherCode": code, "probOfPrecipitation": 40,
+                "totalPrecipAmount": 0.3
+            })
+        };
+        let hours: DataHubResponse<DataHubHour> = serde_json::from_value(serde_json::json!({
+            "type": "FeatureCollection",
+            "features": [{ "properties": { "timeSeries": [
+                hour("2025-01-10T20:00Z", 12.0, 7),
+                hour("2025-01-10T21:00Z", 11.4, 2),
+                hour("2025-01-10T22:00Z", 11.0, 9),
+            ] } }]
+        }))
+        .unwrap();
+        let days: DataHubResponse<DataHubDay> = serde_json::from_value(serde_json::json!({
+            "features": [{ "properties": { "timeSeries": [
+                { "time": "2025-01-10T00:00Z", "nightMinScreenTemperature": 4.1 },
+                { "time": "2025-01-11T00:00Z", "daySignificantWeatherCode": 3,
+                  "dayMaxScreenTemperature": 9.6, "nightMinScreenTemperature": 3.2 }
+            ] } }]
+        }))
+        .unwrap();
+        let config: WeatherConfig = toml::from_str("lat = 51.5\nlong = -0.12").unwrap();
+        let now = "2025-01-10T21:30:00Z".parse().unwrap();
+
+        let weather = to_weather(
+            &hours.features[0].properties.time_series,
+            &days.features[0].properties.time_series,
+            &config,
+            &Localization::default(),
+            now,
+        )
+        .unwrap();
+        assert_eq!(weather.current_temp, 11);
+        assert_eq!(weather.icon, "partly-cloudy-night.svg");
+        assert_eq!((weather.wind_speed, weather.wind_direction), (18, "SW"));
+        assert_eq!(weather.humidity, 81);
+        let hours: Vec<_> = weather.hourly.iter().map(|h| h.hour.as_str()).collect();
+        assert_eq!(hours, ["21", "22"]);
+        assert_eq!(weather.forecast.len(), 1);
+        assert_eq!(
+            (weather.forecast[0].day.as_str(), weather.forecast[0].high),
+            ("Sat", 10)
+        );
+    }
+}
@@ -0,0 +1,510 @@
+mod met_office;
+mod open_meteo;
+mod openweathermap;
+
+use super::DataSource;
+use crate::time::Localization;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use toml::{Table, Value};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub lat: f32,
+    pub long: f32,
+    /// How many hours ahead the hourly forecast goes.
+    #[serde(default = "default_hours")]
+    pub hours: u32,
+    #[serde(default)]
+    pub units: Units,
+    /// Where the forecast comes from.
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// Used whenever `provider` fails.
+    #[serde(default)]
+    pub backup: Option<ProviderKind>,
+    #[serde(default)]
+    pub open_meteo: ProviderConfig,
+    #[serde(default)]
+    pub met_office: ProviderConfig,
+    #[serde(default)]
+    pub openweathermap: ProviderConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum ProviderKind {
+    #[default]
+    #[serde(rename = "open-meteo")]
+    OpenMeteo,
+    /// Met Office Weather DataHub site-specific forecasts, which need an api key.
+    #[serde(rename = "met-office")]
+    MetOffice,
+    /// OpenWeatherMap One Call 3.0, which needs an api key.
+    #[serde(rename = "openweathermap")]
+    OpenWeatherMap,
+}
+
+/// Settings for one provider, in the `[weather.<provider>]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderConfig {
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Overrides the provider's API root.
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// °C, km/h and mm.
+    #[default]
+    Metric,
+    /// °F, mph and inches.
+    Imperial,
+}
+
+impl Units {
+    #[must_use]
+    pub fn wind_speed(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    /// Smallest amount of precipitation worth labelling, about half a millimetre.
+    #[must_use]
+    pub fn notable_precipitation(self) -> f32 {
+        match self {
+            Units::Metric => 0.5,
+            Units::Imperial => 0.02,
+        }
+    }
+
+    /// Decimal places to show precipitation amounts with.
+    #[must_use]
+    pub fn precipitation_decimals(self) -> usize {
+        match self {
+            Units::Metric => 0,
+            Units::Imperial => 2,
+        }
+    }
+
+    /// Converts a temperature from °C.
+    fn temperature(self, celsius: f32) -> f32 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    /// Converts a wind speed from metres per second.
+    fn speed(self, metres_per_second: f32) -> f32 {
+        match self {
+            Units::Metric => metres_per_second * 3.6,
+            Units::Imperial => metres_per_second * 2.237,
+        }
+    }
+
+    /// Converts an amount of rain or snow from mm.
+    fn precipitation(self, mm: f32) -> f32 {
+        match self {
+            Units::Metric => mm,
+            Units::Imperial => mm / 25.4,
+        }
+    }
+}
+
+fn default_hours() -> u32 {
+    12
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Weather {
+    pub current_temp: i32,
+    pub feels_like: i32,
+    pub current_description: String,
+    pub icon: String,
+    /// Relative humidity as a percentage.
+    pub humidity: u8,
+    pub wind_speed: i32,
+    /// Compass point the wind is coming from, e.g. `SW`.
+    pub wind_direction: &'static str,
+    pub uv_index: i32,
+    /// Today's sunrise and sunset, e.g. `06:12`. Empty if the provider doesn't give them.
+    pub sunrise: String,
+    pub sunset: String,
+    pub is_day: bool,
+    pub units: Units,
+    pub forecast: Vec<DayForecast>,
+    /// Starting from the current hour.
+    pub hourly: Vec<HourForecast>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayForecast {
+    pub day: String,
+    pub high: i32,
+    pub low: i32,
+    pub description: String,
+    pub icon: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HourForecast {
+    /// Hour of the day, e.g. `14`.
+    pub hour: String,
+    pub temperature: f32,
+    /// Chance of precipitation as a percentage.
+    pub precipitation_probability: u8,
+    /// Expected precipitation in mm, or inches for imperial units.
+    pub precipitation: f32,
+}
+
+/// Weather conditions that each provider's codes are normalised into, so they share icons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Clear,
+    MainlyClear,
+    PartlyCloudy,
+    Overcast,
+    Haze,
+    Fog,
+    Drizzle,
+    FreezingDrizzle,
+    LightRain,
+    Rain,
+    HeavyRain,
+    FreezingRain,
+    LightSnow,
+    Snow,
+    HeavySnow,
+    BlowingSnow,
+    Sleet,
+    Hail,
+    Thunderstorm,
+    /// Thunderstorm with hail.
+    HeavyThunderstorm,
+    Tornado,
+    Unknown,
+}
+
+impl Condition {
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Condition::Clear => "Clear sky",
+            Condition::MainlyClear => "Mainly clear",
+            Condition::PartlyCloudy => "Partly cloudy",
+            Condition::Overcast => "Overcast",
+            Condition::Haze => "Dust or haze",
+            Condition::Fog => "Fog",
+            Condition::Drizzle => "Drizzle",
+            Condition::FreezingDrizzle => "Freezing drizzle",
+            Condition::LightRain => "Light rain",
+            Condition::Rain => "Moderate rain",
+            Condition::HeavyRain => "Heavy rain",
+            Condition::FreezingRain => "Freezing rain",
+            Condition::LightSnow => "Light snow",
+            Condition::Snow => "Moderate snow",
+            Condition::HeavySnow => "Heavy snow",
+            Condition::BlowingSnow => "Blowing snow",
+            Condition::Sleet => "Sleet",
+            Condition::Hail => "Hail",
+            Condition::Thunderstorm => "Thunderstorm",
+            Condition::HeavyThunderstorm => "Heavy thunderstorm",
+            Condition::Tornado => "Tornado",
+            Condition::Unknown => "Unknown",
+        }
+    }
+
+    /// Icon file under `static/icons`, with night icons where the sky is visible.
+    #[must_use]
+    pub fn icon(self, is_day: bool) -> &'static str {
+        let day_or_night = |day, night| if is_day { day } else { night };
+        match self {
+            Condition::Clear => day_or_night("clear-day.svg", "clear-night.svg"),
+            Condition::MainlyClear => {
+                day_or_night("mostly-clear-day.svg", "mostly-clear-night.svg")
+            }
+            Condition::PartlyCloudy => {
+                day_or_night("partly-cloudy-day.svg", "partly-cloudy-night.svg")
+            }
+            Condition::Overcast => "cloudy.svg",
+            Condition::Haze => "sandstorm.svg",
+            Condition::Fog => "fog.svg",
+            Condition::Drizzle => "drizzle.svg",
+            Condition::FreezingDrizzle => "freezingdrizzle.svg",
+            Condition::LightRain | Condition::Rain | Condition::HeavyRain => "rain.svg",
+            Condition::FreezingRain => "freezingrain.svg",
+            Condition::LightSnow | Condition::Snow | Condition::HeavySnow => "snow.svg",
+            Condition::BlowingSnow => "blowingsnow.svg",
+            // no hail icon, and sleet looks closest
+            Condition::Sleet | Condition::Hail => "sleet.svg",
+            Condition::Thunderstorm => "thunderstorm.svg",
+            Condition::HeavyThunderstorm => "thunderstorm-hail.svg",
+            Condition::Tornado => "tornado.svg",
+            Condition::Unknown => "unknown.svg",
+        }
+    }
+}
+
+/// Eight point compass direction for a bearing in degrees.
+fn compass(degrees: f32) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    POINTS[((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+/// One of the configured providers, ready to fetch from.
+struct Provider {
+    kind: ProviderKind,
+    base_url: String,
+    api_key: String,
+}
+
+impl Provider {
+    fn new(kind: ProviderKind, config: &WeatherConfig) -> anyhow::Result<Self> {
+        let (section, settings, default_base_url, needs_key) = match kind {
+            ProviderKind::OpenMeteo => (
+                "open_meteo",
+                &config.open_meteo,
+                open_meteo::BASE_URL,
+                false,
+            ),
+            ProviderKind::MetOffice => {
+                ("met_office", &config.met_office, met_office::BASE_URL, true)
+            }
+            ProviderKind::OpenWeatherMap => (
+                "openweathermap",
+                &config.openweathermap,
+                openweathermap::BASE_URL,
+                true,
+            ),
+        };
+        let api_key = settings.api_key.clone().unwrap_or_default();
+        if needs_key && api_key.trim().is_empty() {
+            anyhow::bail!("{section}.api_key must be set to use it");
+        }
+        let base_url = settings
+            .base_url
+            .as_deref()
+            .unwrap_or(default_base_url)
+            .trim_end_matches('/')
+            .to_string();
+        reqwest::Url::parse(&base_url)
+            .map_err(|e| anyhow::anyhow!("{section}.base_url '{base_url}' {e}"))?;
+
+        Ok(Self {
+            kind,
+            base_url,
+            api_key,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ProviderKind::OpenMeteo => "Open-Meteo",
+            ProviderKind::MetOffice => "Met Office",
+            ProviderKind::OpenWeatherMap => "OpenWeatherMap",
+        }
+    }
+
+    async fn fetch(
+        &self,
+        client: &Client,
+        config: &WeatherConfig,
+        localization: &Localization,
+    ) -> anyhow::Result<Weather> {
+        match self.kind {
+            ProviderKind::OpenMeteo => {
+                open_meteo::fetch_weather(client, &self.base_url, config, localization).await
+            }
+            ProviderKind::MetOffice => {
+                met_office::fetch_weather(
+                    client,
+                    &self.base_url,
+                    &self.api_key,
+                    config,
+                    localization,
+                )
+                .await
+            }
+            ProviderKind::OpenWeatherMap => {
+                openweathermap::fetch_weather(
+                    client,
+                    &self.base_url,
+                    &self.api_key,
+                    config,
+                    localization,
+                )
+                .await
+            }
+        }
+    }
+}
+
+/// Current conditions and the daily forecast, from the configured provider or its backup.
+pub struct WeatherSource {
+    config: WeatherConfig,
+    localization: Localization,
+    provider: Provider,
+    backup: Option<Provider>,
+}
+
+impl DataSource for WeatherSource {
+    const NAME: &'static str = "weather";
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+    type Config = WeatherConfig;
+    type Output = Weather;
+
+    fn new(config: Self::Config, localization: &Localization) -> anyhow::Result<Self> {
+        if !(-90.0..=90.0).contains(&config.lat) {
+            anyhow::bail!("lat {} is out of range", config.lat);
+        }
+        if !(-180.0..=180.0).contains(&config.long) {
+            anyhow::bail!("long {} is out of range", config.long);
+        }
+        if !(1..=48).contains(&config.hours) {
+            anyhow::bail!("hours must be between 1 and 48, got {}", config.hours);
+        }
+        if config.backup == Some(config.provider) {
+            anyhow::bail!("backup must be a different provider to provider");
+        }
+        Ok(Self {
+            provider: Provider::new(config.provider, &config)?,
+            backup: config
+                .backup
+                .map(|kind| Provider::new(kind, &config))
+                .transpose()?,
+            config,
+            localization: *localization,
+        })
+    }
+
+    async fn fetch(&self, client: &Client) -> anyhow::Result<Self::Output> {
+        let error = match self
+            .provider
+            .fetch(client, &self.config, &self.localization)
+            .await
+        {
+            Ok(weather) => return Ok(weather),
+            Err(e) => e.context(self.provider.name()),
+        };
+        let Some(backup) = &self.backup else {
+            return Err(error);
+        };
+
+        tracing::warn!("{error:#}, falling back to {}", backup.name());
+        backup
+            .fetch(client, &self.config, &self.localization)
+            .await
+            .map_err(|e| anyhow::anyhow!("{error:#}, then {}: {e:#}", backup.name()))
+    }
+}
+
+/// Lets the other sections for the same place, e.g. `[air_quality]`, leave out `lat` and `long`
+/// and use the ones from `[weather]`.
+pub(super) fn share_location(sections: &mut Table, located_sections: &[&str]) {
+    let Some(weather) = sections.get(WeatherSource::NAME).and_then(Value::as_table) else {
+        return;
+    };
+    let location: Vec<_> = ["lat", "long"]
+        .into_iter()
+        .filter_map(|key| weather.get(key).map(|value| (key, value.clone())))
+        .collect();
+    for name in located_sections {
+        if let Some(section) = sections.get_mut(*name).and_then(Value::as_table_mut) {
+            for (key, value) in &location {
+                section.entry(*key).or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_sources::fixture_server;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+
+    #[test]
+    fn picks_night_icons() {
+        assert_eq!(Condition::Clear.icon(true), "clear-day.svg");
+        assert_eq!(Condition::Clear.icon(false), "clear-night.svg");
+        assert_eq!(
+            Condition::PartlyCloudy.icon(false),
+            "partly-cloudy-night.svg"
+        );
+        assert_eq!(Condition::LightRain.icon(false), "rain.svg");
+    }
+
+    #[test]
+    fn points_the_compass() {
+        let points: Vec<_> = [0.0, 22.0, 23.0, 200.0, 350.0, 360.0]
+            .into_iter()
+            .map(compass)
+            .collect();
+        assert_eq!(points, ["N", "N", "NE", "S", "N", "N"]);
+    }
+
+    #[test]
+    fn keyed_providers_need_a_key() {
+        let config = |extra: &str| -> WeatherConfig {
+            toml::from_str(&format!("lat = 51.5\nlong = -0.12\n{extra}")).unwrap()
+        };
+        let localization = Localization::default();
+        assert!(WeatherSource::new(config(""), &localization).is_ok());
+        assert!(WeatherSource::new(config(r#"provider = "met-office""#), &localization).is_err());
+        assert!(
+            WeatherSource::new(
+                config("backup = \"openweathermap\"\n[openweathermap]\napi_key = \"key\""),
+                &localization
+            )
+            .is_ok()
+        );
+        assert!(WeatherSource::new(config(r#"backup = "open-meteo""#), &localization).is_err());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_backup_provider() {
+        async fn one_call() -> axum::Json<serde_json::Value> {
+            axum::Json(openweathermap::tests::one_call())
+        }
+
+        // Open-Meteo is down, OpenWeatherMap isn't
+        let app = axum::Router::new()
+            .route(
+                "/v1/forecast",
+                get(|| async { StatusCode::SERVICE_UNAVAILABLE }),
+            )
+            .route("/data/3.0/onecall", get(one_call));
+        let base_url = fixture_server(app).await;
+
+        let mut config: WeatherConfig = toml::from_str(&format!(
+            r#"
+            lat = 51.5
+            long = -0.12
+            backup = "openweathermap"
+            open_meteo = {{ base_url = "{base_url}" }}
+            openweathermap = {{ base_url = "{base_url}", api_key = "key" }}
+            "#
+        ))
+        .unwrap();
+        let localization = Localization::default();
+        let source = WeatherSource::new(config.clone(), &localization).unwrap();
+        let weather = source.fetch(&Client::new()).await.unwrap();
+        assert_eq!(weather.current_description, "Light rain");
+
+        // and both failing reports both
+        config.openweathermap.base_url = Some(format!("{base_url}/missing"));
+        let source = WeatherSource::new(config, &localization).unwrap();
+        let error = source.fetch(&Client::new()).await.unwrap_err().to_string();
+        assert!(error.contains("Open-Meteo") && error.contains("OpenWeatherMap"));
+    }
+}
@@ -0,0 +1,293 @@
+use super::{Condition, DayForecast, HourForecast, Units, Weather, WeatherConfig, compass};
+use crate::time::Localization;
+use chrono::{NaiveDate, NaiveDateTime};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Deserializer};
+
+pub(super) const BASE_URL: &str = "https://api.open-meteo.com";
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoResponse {
+    current: OpenMeteoCurrent,
+    hourly: OpenMeteoHourly,
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoHourly {
+    #[serde(deserialize_with = "deserialize_times")]
+    time: Vec<NaiveDateTime>,
+    temperature_2m: Vec<f32>,
+    precipitation_probability: Vec<Option<u8>>,
+    precipitation: Vec<Option<f32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoCurrent {
+    temperature_2m: f32,
+    apparent_temperature: f32,
+    relative_humidity_2m: u8,
+    wind_speed_10m: f32,
+    wind_direction_10m: f32,
+    uv_index: Option<f32>,
+    weather_code: u8,
+    is_day: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenMeteoDaily {
+    time: Vec<NaiveDate>,
+    weather_code: Vec<u8>,
+    temperature_2m_max: Vec<f32>,
+    temperature_2m_min: Vec<f32>,
+    #[serde(deserialize_with = "deserialize_times")]
+    sunrise: Vec<NaiveDateTime>,
+    #[serde(deserialize_with = "deserialize_times")]
+    sunset: Vec<NaiveDateTime>,
+}
+
+/// Open-Meteo gives local times without seconds, e.g. `2025-01-10T08:01`.
+fn deserialize_times<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<NaiveDateTime>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|t| {
+            NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M").map_err(serde::de::Error::custom)
+        })
+        .collect()
+}
+
+/// Condition for a WMO weather code.
+fn condition(code: u8) -> Condition {
+    match code {
+        0 => Condition::Clear,
+        1 => Condition::MainlyClear,
+        2 => Condition::PartlyCloudy,
+        3 => Condition::Overcast,
+        4..=9 | 30..=35 => Condition::Haze,
+        10..=12 | 40..=49 => Condition::Fog,
+        50..=55 => Condition::Drizzle,
+        56..=57 => Condition::FreezingDrizzle,
+        60..=61 | 80 => Condition::LightRain,
+        62..=63 | 81 => Condition::Rain,
+        64..=65 | 82 => Condition::HeavyRain,
+        66..=67 => Condition::FreezingRain,
+        70..=71 | 85 => Condition::LightSnow,
+        72..=73 | 86 => Condition::Snow,
+        74..=75 => Condition::HeavySnow,
+        77 | 36..=39 => Condition::BlowingSnow,
+        68..=69 | 83..=84 | 79 | 87..=88 => Condition::Sleet,
+        95..=96 => Condition::Thunderstorm,
+        99 => Condition::HeavyThunderstorm,
+        19 => Condition::Tornado,
+
+        _ => Condition::Unknown,
+    }
+}
+
+pub(super) async fn fetch_weather(
+    client: &Client,
+    base_url: &str,
+    config: &WeatherConfig,
+    localization: &Localization,
+) -> Result<Weather, anyhow::Error> {
+    let mut url = Url::parse_with_params(
+        &format!("{base_url}/v1/forecast"),
+        [
+            ("latitude", config.lat.to_string().as_str()),
+            ("longitude", config.long.to_string().as_str()),
+            (
+                "current",
+                "temperature_2m,apparent_temperature,relative_humidity_2m,wind_speed_10m,\
+                 wind_direction_10m,uv_index,weather_code,is_day",
+            ),
+            (
+                "hourly",
+                "temperature_2m,precipitation_probability,precipitation",
+            ),
+            ("forecast_hours", config.hours.to_string().as_str()),
+            (
+                "daily",
+                "weather_code,temperature_2m_max,temperature_2m_min,sunrise,sunset",
+            ),
+            ("timezone", localization.tz.name()),
+        ],
+    )?;
+    if config.units == Units::Imperial {
+        url.query_pairs_mut()
+            .append_pair("temperature_unit", "fahrenheit")
+            .append_pair("wind_speed_unit", "mph")
+            .append_pair("precipitation_unit", "inch");
+    }
+
+    let response: OpenMeteoResponse = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(to_weather(response, config.units, localization))
+}
+
+fn to_weather(response: OpenMeteoResponse, units: Units, localization: &Localization) -> Weather {
+    let forecast: Vec<DayForecast> = response
+        .daily
+        .time
+        .iter()
+        .zip(response.daily.weather_code.iter())
+        .zip(response.daily.temperature_2m_max.iter())
+        .zip(response.daily.temperature_2m_min.iter())
+        .skip(1) // Skip today
+        .take(5) // Next 5 days
+        .map(|(((date, &code), &high), &low)| {
+            let condition = condition(code);
+            DayForecast {
+                day: localization.format_date(*date, "%a"),
+                high: high.round() as i32,
+                low: low.round() as i32,
+                description: condition.description().to_string(),
+                icon: condition.icon(true).to_string(),
+            }
+        })
+        .collect();
+
+    let hourly = response
+        .hourly
+        .time
+        .iter()
+        .zip(response.hourly.temperature_2m.iter())
+        .zip(response.hourly.precipitation_probability.iter())
+        .zip(response.hourly.precipitation.iter())
+        .map(
+            |(((time, &temperature), &probability), &amount)| HourForecast {
+                hour: time.format("%H").to_string(),
+                temperature,
+                precipitation_probability: probability.unwrap_or(0),
+                precipitation: amount.unwrap_or(0.0),
+            },
+        )
+        .collect();
+
+    let current = response.current;
+    let is_day = current.is_day == 1;
+    let condition = condition(current.weather_code);
+    // times come back in the configured timezone already
+    let time_of = |times: &[NaiveDateTime]| {
+        times
+            .first()
+            .map(|t| t.format("%H:%M").to_string())
+            .unwrap_or_default()
+    };
+
+    Weather {
+        current_temp: current.temperature_2m.round() as i32,
+        feels_like: current.apparent_temperature.round() as i32,
+        current_description: condition.description().to_string(),
+        icon: condition.icon(is_day).to_string(),
+        humidity: current.relative_humidity_2m,
+        wind_speed: current.wind_speed_10m.round() as i32,
+        wind_direction: compass(current.wind_direction_10m),
+        uv_index: current.uv_index.unwrap_or(0.0).round() as i32,
+        sunrise: time_of(&response.daily.sunrise),
+        sunset: time_of(&response.daily.sunset),
+        is_day,
+        units,
+        forecast,
+        hourly,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed from a real response to the query in `fetch_weather`.
+    const RESPONSE: &str = r#"{
+        "latitude": 51.5, "longitude": -0.120000124, "generationtime_ms": 0.0890493392944336,
+        "utc_offset_seconds": 0, "timezone": "Europe/London", "timezone_abbreviation": "GMT",
+        "elevation": 23.0,
+        "current_units": {
+            "time": "iso8601", "interval": "seconds", "temperature_2m": "°C",
+            "apparent_temperature": "°C", "relative_humidity_2m": "%", "wind_speed_10m": "km/h",
+            "wind_direction_10m": "°", "uv_index": "", "weather_code": "wmo code", "is_day": ""
+        },
+        "current": {
+            "time": "2025-01-10T14:15", "interval": 900, "temperature_2m": 4.3,
+            "apparent_temperature": 0.6, "relative_humidity_2m": 76, "wind_speed_10m": 14.8,
+            "wind_direction_10m": 257, "uv_index": 0.45, "weather_code": 3, "is_day": 1
+        },
+        "hourly_units": { "time": "iso8601", "temperature_2m": "°C", "precipitation_probability": "%", "precipitation": "mm" },
+        "hourly": {
+            "time": ["2025-01-10T14:00", "2025-01-10T15:00", "2025-01-10T16:00"],
+            "temperature_2m": [4.3, 4.0, 3.2],
+            "precipitation_probability": [10, 35, null],
+            "precipitation": [0.0, 0.4, null]
+        },
+        "daily_units": {
+            "time": "iso8601", "weather_code": "wmo code", "temperature_2m_max": "°C",
+            "temperature_2m_min": "°C", "sunrise": "iso8601", "sunset": "iso8601"
+        },
+        "daily": {
+            "time": ["2025-01-10", "2025-01-11"],
+            "weather_code": [3, 61],
+            "temperature_2m_max": [5.1, 7.8],
+            "temperature_2m_min": [0.9, 2.4],
+            "sunrise": ["2025-01-10T08:01", "2025-01-11T08:00"],
+            "sunset": ["2025-01-10T16:14", "2025-01-11T16:15"]
+        }
+    }"#;
+
+    #[test]
+    fn deserializes_open_meteo_response() {
+        let response: OpenMeteoResponse = serde_json::from_str(RESPONSE).unwrap();
+
+        assert_eq!(
+            response.hourly.time[1],
+            NaiveDateTime::parse_from_str("2025-01-10 15:00", "%Y-%m-%d %H:%M").unwrap()
+        );
+        assert_eq!(response.hourly.precipitation_probability[2], None);
+        assert_eq!(
+            response.daily.time[1],
+            NaiveDate::from_ymd_opt(2025, 1, 11).unwrap()
+        );
+        assert_eq!(response.current.weather_code, 3);
+    }
+
+    #[test]
+    fn reads_current_conditions() {
+        let response: OpenMeteoResponse = serde_json::from_value(serde_json::json!({
+            "current": {
+                "temperature_2m": 52.3, "apparent_temperature": 48.6,
+                "relative_humidity_2m": 81, "wind_speed_10m": 11.4,
+                "wind_direction_10m": 225.0, "uv_index": 0.0,
+                "weather_code": 1, "is_day": 0
+            },
+            "hourly": {
+                "time": ["2025-01-10T22:00"], "temperature_2m": [51.8],
+                "precipitation_probability": [null], "precipitation": [0.0]
+            },
+            "daily": {
+                "time": ["2025-01-10", "2025-01-11"], "weather_code": [1, 3],
+                "temperature_2m_max": [54.0, 50.1], "temperature_2m_min": [44.2, 41.0],
+                "sunrise": ["2025-01-10T08:01", "2025-01-11T08:00"],
+                "sunset": ["2025-01-10T16:14", "2025-01-11T16:15"]
+            }
+        }))
+        .unwrap();
+
+        let weather = to_weather(response, Units::Imperial, &Localization::default());
+        assert_eq!((weather.current_temp, weather.feels_like), (52, 49));
+        assert_eq!(weather.icon, "mostly-clear-night.svg");
+        assert_eq!((weather.wind_speed, weather.wind_direction), (11, "SW"));
+        assert_eq!(weather.units.wind_speed(), "mph");
+        assert_eq!(
+            (weather.sunrise.as_str(), weather.sunset.as_str()),
+            ("08:01", "16:14")
+        );
+        assert_eq!(weather.forecast.len(), 1);
+        assert_eq!(weather.forecast[0].description, "Overcast");
+        assert_eq!(weather.hourly[0].precipitation_probability, 0);
+    }
+}
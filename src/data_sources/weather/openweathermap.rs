@@ -0,0 +1,252 @@
+use super::{Condition, DayForecast, HourForecast, Weather, WeatherConfig, compass};
+use crate::time::Localization;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub(super) const BASE_URL: &str = "https://api.openweathermap.org";
+
+#[derive(Debug, Deserialize)]
+struct OneCallResponse {
+    current: OneCallCurrent,
+    #[serde(default)]
+    hourly: Vec<OneCallHour>,
+    #[serde(default)]
+    daily: Vec<OneCallDay>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OneCallCurrent {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    sunrise: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    sunset: DateTime<Utc>,
+    temp: f32,
+    feels_like: f32,
+    humidity: u8,
+    #[serde(default)]
+    uvi: f32,
+    wind_speed: f32,
+    wind_deg: f32,
+    weather: Vec<OneCallCondition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OneCallHour {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    dt: DateTime<Utc>,
+    temp: f32,
+    /// Chance of precipitation from 0 to 1.
+    #[serde(default)]
+    pop: f32,
+    #[serde(default)]
+    rain: HashMap<String, f32>,
+    #[serde(default)]
+    snow: HashMap<String, f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OneCallDay {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    dt: DateTime<Utc>,
+    temp: OneCallTemperatures,
+    weather: Vec<OneCallCondition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OneCallTemperatures {
+    min: f32,
+    max: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct OneCallCondition {
+    id: u16,
+    /// e.g. `10d`, the suffix says whether it is day or night.
+    icon: String,
+}
+
+impl OneCallCondition {
+    fn is_day(&self) -> bool {
+        !self.icon.ends_with('n')
+    }
+}
+
+/// Condition for an OpenWeatherMap weather condition id.
+fn condition(id: u16) -> Condition {
+    match id {
+        200..=202 | 230..=232 => Condition::Thunderstorm,
+        210..=221 => Condition::HeavyThunderstorm,
+        300..=310 => Condition::Drizzle,
+        311..=321 => Condition::LightRain,
+        500 | 520 => Condition::LightRain,
+        501 | 521 => Condition::Rain,
+        502..=504 | 522 | 531 => Condition::HeavyRain,
+        511 => Condition::FreezingRain,
+        600 | 620 => Condition::LightSnow,
+        601 | 621 => Condition::Snow,
+        602 | 622 => Condition::HeavySnow,
+        611..=616 => Condition::Sleet,
+        701 | 741 => Condition::Fog,
+        711 | 721 | 731 | 751 | 761 | 762 => Condition::Haze,
+        771 => Condition::HeavyRain,
+        781 => Condition::Tornado,
+        800 => Condition::Clear,
+        801 => Condition::MainlyClear,
+        802 => Condition::PartlyCloudy,
+        803..=804 => Condition::Overcast,
+
+        _ => Condition::Unknown,
+    }
+}
+
+pub(super) async fn fetch_weather(
+    client: &Client,
+    base_url: &str,
+    api_key: &str,
+    config: &WeatherConfig,
+    localization: &Localization,
+) -> Result<Weather, anyhow::Error> {
+    let url = Url::parse_with_params(
+        &format!("{base_url}/data/3.0/onecall"),
+        [
+            ("lat", config.lat.to_string().as_str()),
+            ("lon", config.long.to_string().as_str()),
+            ("appid", api_key),
+            // converted here, as the imperial units give wind speeds in mph but rain in mm
+            ("units", "metric"),
+            ("exclude", "minutely,alerts"),
+        ],
+    )?;
+
+    let response: OneCallResponse = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    to_weather(response, config, localization)
+}
+
+fn to_weather(
+    response: OneCallResponse,
+    config: &WeatherConfig,
+    localization: &Localization,
+) -> anyhow::Result<Weather> {
+    let units = config.units;
+    let forecast = response
+        .daily
+        .iter()
+        .skip(1) // Skip today
+        .take(5) // Next 5 days
+        .map(|day| {
+            let condition = condition(day.weather.first().map_or(0, |w| w.id));
+            DayForecast {
+                day: localization.format(&day.dt, "%a"),
+                high: units.temperature(day.temp.max).round() as i32,
+                low: units.temperature(day.temp.min).round() as i32,
+                description: condition.description().to_string(),
+                icon: condition.icon(true).to_string(),
+            }
+        })
+        .collect();
+
+    let hourly = response
+        .hourly
+        .iter()
+        .take(config.hours as usize)
+        .map(|hour| HourForecast {
+            hour: localization.format(&hour.dt, "%H"),
+            temperature: units.temperature(hour.temp),
+            precipitation_probability: (hour.pop * 100.0).round() as u8,
+            precipitation: units.precipitation(
+                hour.rain.get("1h").unwrap_or(&0.0) + hour.snow.get("1h").unwrap_or(&0.0),
+            ),
+        })
+        .collect();
+
+    let current = response.current;
+    let now = current
+        .weather
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("No current conditions in the forecast"))?;
+    let is_day = now.is_day();
+    let condition = condition(now.id);
+
+    Ok(Weather {
+        current_temp: units.temperature(current.temp).round() as i32,
+        feels_like: units.temperature(current.feels_like).round() as i32,
+        current_description: condition.description().to_string(),
+        icon: condition.icon(is_day).to_string(),
+        humidity: current.humidity,
+        wind_speed: units.speed(current.wind_speed).round() as i32,
+        wind_direction: compass(current.wind_deg),
+        uv_index: current.uvi.round() as i32,
+        sunrise: localization.format(&current.sunrise, "%H:%M"),
+        sunset: localization.format(&current.sunset, "%H:%M"),
+        is_day,
+        units,
+        forecast,
+        hourly,
+    })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A trimmed One Call response for an evening in London in January.
+    pub(in super::super) fn one_call() -> serde_json::Value {
+        serde_json::json!({
+            "lat": 51.5, "lon": -0.12, "timezone": "Europe/London", "timezone_offset": 0,
+            "current": {
+                "dt": 1_736_546_400, "sunrise": 1_736_496_060, "sunset": 1_736_525_640,
+                "temp": 11.3, "feels_like": 9.6, "humidity": 81, "uvi": 0,
+                "wind_speed": 5.0, "wind_deg": 225,
+                "weather": [{ "id": 500, "main": "Rain", "description": "light rain", "icon": "10n" }]
+            },
+            "hourly": [
+                { "dt": 1_736_546_400, "temp": 11.3, "pop": 0.62, "rain": { "1h": 0.4 },
+                  "weather": [{ "id": 500, "icon": "10n" }] },
+                { "dt": 1_736_550_000, "temp": 10.9, "pop": 0.2,
+                  "weather": [{ "id": 804, "icon": "04n" }] }
+            ],
+            "daily": [
+                { "dt": 1_736_506_800, "temp": { "min": 6.2, "max": 12.0 },
+                  "weather": [{ "id": 500, "icon": "10d" }] },
+                { "dt": 1_736_593_200, "temp": { "min": 4.8, "max": 9.4 },
+                  "weather": [{ "id": 802, "icon": "03d" }] }
+            ]
+        })
+    }
+
+    #[test]
+    fn reads_one_call() {
+        let response: OneCallResponse = serde_json::from_value(one_call()).unwrap();
+        let config: WeatherConfig = toml::from_str("lat = 51.5\nlong = -0.12\nhours = 1").unwrap();
+
+        let weather = to_weather(response, &config, &Localization::default()).unwrap();
+        assert_eq!((weather.current_temp, weather.feels_like), (11, 10));
+        assert_eq!(weather.icon, "rain.svg");
+        assert!(!weather.is_day);
+        // 5 m/s
+        assert_eq!((weather.wind_speed, weather.wind_direction), (18, "SW"));
+        assert_eq!(
+            (weather.sunrise.as_str(), weather.sunset.as_str()),
+            ("08:01", "16:14")
+        );
+        assert_eq!(weather.hourly.len(), 1);
+        assert_eq!(
+            (
+                weather.hourly[0].hour.as_str(),
+                weather.hourly[0].precipitation_probability
+            ),
+            ("22", 62)
+        );
+        assert_eq!(weather.forecast.len(), 1);
+        assert_eq!(weather.forecast[0].day, "Sat");
+        assert_eq!(weather.forecast[0].icon, "partly-cloudy-day.svg");
+    }
+}
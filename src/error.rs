@@ -15,8 +15,9 @@ pub enum AppError {
     #[error("Axum http error")]
     AxumHttp(#[from] axum::http::Error),
 
-    #[error("Error loading page")]
-    Render(#[from] askama::Error),
+    /// A page template failed, with where in the template when known.
+    #[error("Error rendering page: {0}")]
+    Render(Cow<'static, str>),
 
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -31,6 +32,39 @@ pub enum AppError {
     Anyhow(#[from] anyhow::Error),
 }
 
+impl From<askama::Error> for AppError {
+    fn from(e: askama::Error) -> Self {
+        AppError::Render(e.to_string().into())
+    }
+}
+
+impl From<minijinja::Error> for AppError {
+    /// Includes the template and line, and those of any template it included that failed.
+    fn from(e: minijinja::Error) -> Self {
+        fn describe(e: &minijinja::Error) -> String {
+            let detail = e
+                .detail()
+                .map_or_else(|| e.kind().to_string(), ToString::to_string);
+            match (e.name(), e.line()) {
+                (Some(name), Some(line)) => format!("{detail} ({name} line {line})"),
+                (Some(name), None) => format!("{detail} ({name})"),
+                _ => detail,
+            }
+        }
+
+        let mut message = describe(&e);
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            match cause.downcast_ref::<minijinja::Error>() {
+                Some(cause) => message.push_str(&format!(", caused by {}", describe(cause))),
+                None => message.push_str(&format!(", caused by {cause}")),
+            }
+            source = cause.source();
+        }
+        AppError::Render(message.into())
+    }
+}
+
 impl AppError {
     fn status_code(&self) -> StatusCode {
         match self {
@@ -63,6 +97,9 @@ impl IntoResponse for AppError {
             AppError::Anyhow(ref e) => {
                 tracing::error!("Generic error: {:?}", e);
             }
+            AppError::Render(ref e) => {
+                tracing::error!("Render error: {e}");
+            }
             _ => (),
         }
 
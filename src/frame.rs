@@ -2,11 +2,26 @@ use crate::controller::{Inky, LedState};
 use crate::{AppError, FrameAppState};
 use axum::body::Bytes;
 use axum::debug_handler;
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::http::StatusCode;
 use image::load_from_memory_with_format;
+use serde::Deserialize;
 use std::time::Duration;
 
+fn default_dither() -> bool {
+    true
+}
+
+/// How to show an image sent to the frame.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DisplayOptions {
+    #[serde(default = "default_dither")]
+    pub dither: bool,
+    /// From 0 to 1, for this image only.
+    #[serde(default)]
+    pub saturation: Option<f32>,
+}
+
 #[debug_handler]
 pub async fn health_check(State(app_state): State<FrameAppState>) -> Result<StatusCode, AppError> {
     let mut i = app_state.inky.lock().expect("mutex poisoned");
@@ -31,6 +46,7 @@ pub async fn blink(State(app_state): State<FrameAppState>) -> Result<StatusCode,
 #[debug_handler]
 pub async fn set_to_page(
     State(app_state): State<FrameAppState>,
+    Query(options): Query<DisplayOptions>,
     body: Bytes,
 ) -> Result<StatusCode, AppError> {
     let mut image = load_from_memory_with_format(&body, image::ImageFormat::Png)
@@ -45,7 +61,21 @@ pub async fn set_to_page(
     }
 
     let mut inky = app_state.inky.lock().expect("mutex poisoned");
-    inky.set_display(&mut image, true)?;
+    let Some(saturation) = options.saturation else {
+        inky.set_display(&mut image, options.dither)?;
+        return Ok(StatusCode::OK);
+    };
+
+    if !(0.0..=1.0).contains(&saturation) {
+        return Err(AppError::InvalidInput(
+            format!("Saturation should be between 0 and 1, got: '{saturation}'").into(),
+        ));
+    }
+    let previous = inky.saturation();
+    inky.set_saturation(saturation)?;
+    let res = inky.set_display(&mut image, options.dither);
+    inky.set_saturation(previous)?;
+    res?;
     Ok(StatusCode::OK)
 }
 
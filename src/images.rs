@@ -0,0 +1,417 @@
+use crate::photos::{EXTENSIONS, PhotoFrame, scan, sidecar_path, taken_on};
+use crate::{AppError, ServerAppState};
+use anyhow::Context;
+use axum::Json;
+use axum::debug_handler;
+use axum::extract::{Multipart, Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use chrono::{DateTime, NaiveDate, Utc};
+use image::ImageFormat;
+use image::codecs::jpeg::JpegEncoder;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Longest side of the thumbnails in the image list.
+const THUMBNAIL_SIZE: u32 = 240;
+
+/// Largest image that can be uploaded, so a small file can't decode to gigabytes of pixels.
+const MAX_PIXELS: u64 = 50_000_000;
+
+/// Longest name allowed for an uploaded or renamed file, extension included.
+const MAX_NAME_LEN: usize = 100;
+
+/// An image in the library.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageInfo {
+    /// Path relative to the photos directory, used to display, rename or delete it.
+    pub name: String,
+    /// In bytes.
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// When the photo was taken, from its EXIF data.
+    pub taken: Option<NaiveDate>,
+    /// Whether it has a sidecar file with its own display settings.
+    pub has_settings: bool,
+    pub thumbnail: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameRequest {
+    pub name: String,
+}
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidInput(message.into().into())
+}
+
+/// Cleans up one part of a name, keeping letters, digits, `-`, `_` and `.`, and replacing
+/// runs of anything else with `-`.
+fn sanitise_part(part: &str) -> String {
+    let mut clean = String::with_capacity(part.len());
+    for c in part.trim().chars() {
+        let c = if c.is_alphanumeric() || matches!(c, '_' | '.') {
+            c
+        } else {
+            '-'
+        };
+        if !(c == '-' && clean.ends_with('-')) {
+            clean.push(c);
+        }
+    }
+    // no hidden files, `..` or names that look like flags
+    clean
+        .trim_start_matches(['.', '-'])
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// Sanitises a new name for an image, which may include folders, e.g. `holiday/beach.jpg`.
+/// The extension is lowercased and must be one of the supported image types.
+fn sanitise_name(name: &str) -> Result<String, AppError> {
+    let parts: Vec<_> = name
+        .split(['/', '\\'])
+        .map(sanitise_part)
+        .filter(|p| !p.is_empty())
+        .collect();
+    let Some((file, folders)) = parts.split_last() else {
+        return Err(invalid(format!("Invalid image name: '{name}'")));
+    };
+
+    let (stem, extension) = file
+        .rsplit_once('.')
+        .map(|(stem, ext)| (stem.trim_end_matches(['.', '-']), ext.to_ascii_lowercase()))
+        .filter(|(stem, ext)| !stem.is_empty() && EXTENSIONS.contains(&ext.as_str()))
+        .ok_or_else(|| {
+            invalid(format!(
+                "Image names must end in one of {}, got: '{name}'",
+                EXTENSIONS.join(", ")
+            ))
+        })?;
+    let file = format!("{stem}.{extension}");
+    if file.chars().count() > MAX_NAME_LEN {
+        return Err(invalid(format!(
+            "Image names can be at most {MAX_NAME_LEN} characters"
+        )));
+    }
+
+    Ok(folders
+        .iter()
+        .map(String::as_str)
+        .chain([file.as_str()])
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Checks the upload is a supported image that isn't too big, and that its extension matches.
+fn check_upload(name: &str, bytes: &[u8], max_bytes: usize) -> Result<(), AppError> {
+    if bytes.len() > max_bytes {
+        return Err(invalid(format!(
+            "Images can be at most {} MB",
+            max_bytes / 1024 / 1024
+        )));
+    }
+    let format = match image::guess_format(bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg)) => format,
+        _ => return Err(invalid("Only PNG and JPEG images are supported")),
+    };
+    let extension = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    if !format.extensions_str().contains(&extension) {
+        return Err(invalid(format!(
+            "'{name}' is a {format:?} image, expected it to end in .{}",
+            format.extensions_str()[0]
+        )));
+    }
+
+    let (width, height) = image::ImageReader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|e| invalid(format!("Couldn't read image: {e}")))?;
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(invalid(format!(
+            "Images can be at most {} megapixels, got {width}x{height}",
+            MAX_PIXELS / 1_000_000
+        )));
+    }
+    Ok(())
+}
+
+/// Saves an uploaded image under its sanitised name.
+fn save(frame: &PhotoFrame, file_name: &str, bytes: &[u8]) -> Result<ImageInfo, AppError> {
+    // browsers send just the file name, but other clients may include their own folders
+    let base_name = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name = sanitise_name(base_name)?;
+    check_upload(&name, bytes, frame.config().max_upload_bytes())?;
+
+    let path = frame.resolve(&name)?;
+    if path.exists() {
+        return Err(invalid(format!("An image called '{name}' already exists")));
+    }
+    std::fs::create_dir_all(&frame.config().dir)?;
+    // written under a hidden name first, so the photo frame never picks up half an image
+    let partial = frame.config().dir.join(format!(".upload-{name}"));
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, &path)?;
+
+    tracing::info!("Uploaded image '{name}'");
+    Ok(info(name, path))
+}
+
+fn info(name: String, path: PathBuf) -> ImageInfo {
+    let metadata = std::fs::metadata(&path).ok();
+    let dimensions = image::image_dimensions(&path).ok();
+    let mut thumbnail =
+        Url::parse("http://localhost/api/images/thumbnail/").expect("thumbnail base url is valid");
+    thumbnail
+        .path_segments_mut()
+        .expect("thumbnail base url has a path")
+        .pop_if_empty()
+        .extend(name.split('/'));
+
+    ImageInfo {
+        size: metadata.as_ref().map_or(0, std::fs::Metadata::len),
+        modified: metadata
+            .and_then(|m| m.modified().ok())
+            .map(DateTime::<Utc>::from),
+        width: dimensions.map(|(w, _)| w),
+        height: dimensions.map(|(_, h)| h),
+        taken: taken_on(&path),
+        has_settings: sidecar_path(&path).is_file(),
+        thumbnail: thumbnail.path().to_string(),
+        name,
+    }
+}
+
+/// Every image in the library, by name.
+fn list(frame: &PhotoFrame) -> Result<Vec<ImageInfo>, AppError> {
+    let dir = &frame.config().dir;
+    let mut paths = Vec::new();
+    if dir.exists() {
+        scan(dir, &mut paths)?;
+    }
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.strip_prefix(dir).ok()?.to_str()?.replace('\\', "/");
+            Some(info(name, path))
+        })
+        .collect())
+}
+
+/// Renames an image and its sidecar file, moving it between folders if the new name says to.
+fn rename(frame: &PhotoFrame, name: &str, new_name: &str) -> Result<ImageInfo, AppError> {
+    let from = frame.resolve(name)?;
+    if !from.is_file() {
+        return Err(AppError::NotFound);
+    }
+    let new_name = sanitise_name(new_name)?;
+    let to = frame.resolve(&new_name)?;
+    if to.exists() {
+        return Err(invalid(format!(
+            "An image called '{new_name}' already exists"
+        )));
+    }
+    if ImageFormat::from_path(&from).ok() != ImageFormat::from_path(&to).ok() {
+        return Err(invalid("Renaming can't change the type of an image"));
+    }
+
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&from, &to)?;
+    let sidecar = sidecar_path(&from);
+    if sidecar.exists() {
+        std::fs::rename(&sidecar, sidecar_path(&to))?;
+    }
+
+    tracing::info!("Renamed image '{name}' to '{new_name}'");
+    Ok(info(new_name, to))
+}
+
+/// Deletes an image and its sidecar file.
+fn delete(frame: &PhotoFrame, name: &str) -> Result<(), AppError> {
+    let path = frame.resolve(name)?;
+    if !path.is_file() {
+        return Err(AppError::NotFound);
+    }
+    std::fs::remove_file(&path)?;
+    let sidecar = sidecar_path(&path);
+    if sidecar.exists() {
+        std::fs::remove_file(sidecar)?;
+    }
+    tracing::info!("Deleted image '{name}'");
+    Ok(())
+}
+
+fn thumbnail(frame: &PhotoFrame, name: &str) -> Result<Vec<u8>, AppError> {
+    let photo = frame.photo(name)?;
+    if !photo.path.is_file() {
+        return Err(AppError::NotFound);
+    }
+    let thumbnail = photo
+        .decode()?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+
+    let mut buffer = Vec::new();
+    JpegEncoder::new_with_quality(&mut buffer, 80)
+        .encode_image(&thumbnail)
+        .context("Failed to encode thumbnail")?;
+    Ok(buffer)
+}
+
+/// Runs a library operation off the async runtime, as it blocks on the filesystem.
+async fn blocking<T: Send + 'static>(
+    state: &ServerAppState,
+    f: impl FnOnce(&PhotoFrame) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let frame: Arc<PhotoFrame> = state.photos.clone();
+    tokio::task::spawn_blocking(move || f(&frame))
+        .await
+        .context("Image library task panicked")?
+}
+
+#[debug_handler]
+pub async fn list_images(
+    State(state): State<ServerAppState>,
+) -> Result<Json<Vec<ImageInfo>>, AppError> {
+    Ok(Json(blocking(&state, list).await?))
+}
+
+/// Saves each file in the `image` field of a multipart form.
+#[debug_handler]
+pub async fn upload_images(
+    State(state): State<ServerAppState>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Vec<ImageInfo>>), AppError> {
+    let mut saved = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| invalid(format!("Invalid upload: {e}")))?
+    {
+        if field.name() != Some("image") {
+            continue;
+        }
+        let file_name = field
+            .file_name()
+            .ok_or_else(|| invalid("Uploaded images need a file name"))?
+            .to_string();
+        let bytes = field
+            .bytes()
+            .await
+            .map_err(|e| invalid(format!("Invalid upload: {e}")))?;
+        saved.push(blocking(&state, move |frame| save(frame, &file_name, &bytes)).await?);
+    }
+
+    if saved.is_empty() {
+        return Err(invalid("No files in the 'image' field"));
+    }
+    Ok((StatusCode::CREATED, Json(saved)))
+}
+
+#[debug_handler]
+pub async fn image_thumbnail(
+    State(state): State<ServerAppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let jpeg = blocking(&state, move |frame| thumbnail(frame, &name)).await?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], jpeg))
+}
+
+#[debug_handler]
+pub async fn rename_image(
+    State(state): State<ServerAppState>,
+    Path(name): Path<String>,
+    Json(request): Json<RenameRequest>,
+) -> Result<Json<ImageInfo>, AppError> {
+    Ok(Json(
+        blocking(&state, move |frame| rename(frame, &name, &request.name)).await?,
+    ))
+}
+
+#[debug_handler]
+pub async fn delete_image(
+    State(state): State<ServerAppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AppError> {
+    blocking(&state, move |frame| delete(frame, &name)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photos::PhotosConfig;
+    use image::ImageEncoder;
+    use image::codecs::png::PngEncoder;
+
+    fn png() -> Vec<u8> {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(&[255; 4 * 3 * 3], 4, 3, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn sanitises_names() {
+        assert_eq!(
+            sanitise_name("Beach Day (1).PNG").unwrap(),
+            "Beach-Day-1.png"
+        );
+        assert_eq!(
+            sanitise_name("../../etc/passwd.jpg").unwrap(),
+            "etc/passwd.jpg"
+        );
+        assert_eq!(
+            sanitise_name("holiday//.hidden.jpeg").unwrap(),
+            "holiday/hidden.jpeg"
+        );
+        assert!(sanitise_name("notes.txt").is_err());
+        assert!(sanitise_name("...").is_err());
+        assert!(sanitise_name(&format!("{}.png", "a".repeat(MAX_NAME_LEN))).is_err());
+    }
+
+    #[test]
+    fn uploads_renames_and_deletes() {
+        let dir = std::env::temp_dir().join("inky_display_images_library");
+        let _ = std::fs::remove_dir_all(&dir);
+        let frame = PhotoFrame::new(PhotosConfig {
+            dir: dir.clone(),
+            ..PhotosConfig::default()
+        });
+
+        let saved = save(&frame, "C:\\Users\\me\\My Photo.png", &png()).unwrap();
+        assert_eq!(saved.name, "My-Photo.png");
+        assert_eq!((saved.width, saved.height), (Some(4), Some(3)));
+        assert_eq!(saved.thumbnail, "/api/images/thumbnail/My-Photo.png");
+        assert!(save(&frame, "My Photo.png", &png()).is_err());
+        assert!(save(&frame, "photo.jpg", &png()).is_err());
+        assert!(save(&frame, "photo.png", b"not an image").is_err());
+
+        std::fs::write(dir.join("My-Photo.png.toml"), "fit = \"cover\"").unwrap();
+        let renamed = rename(&frame, "My-Photo.png", "garden/roses.png").unwrap();
+        assert_eq!(renamed.thumbnail, "/api/images/thumbnail/garden/roses.png");
+        assert!(renamed.has_settings);
+        assert!(rename(&frame, "garden/roses.png", "roses.jpg").is_err());
+
+        assert!(matches!(
+            frame.resolve("../config.toml"),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            delete(&frame, "My-Photo.png"),
+            Err(AppError::NotFound)
+        ));
+        delete(&frame, "garden/roses.png").unwrap();
+        assert!(list(&frame).unwrap().is_empty());
+        assert!(!dir.join("garden/roses.png.toml").exists());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::config::parse_duration;
+use crate::controller::Inky;
+use crate::{AppError, ServerAppState, comm};
+use axum::Json;
+use axum::debug_handler;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Page that shows the message.
+pub const PAGE: &str = "large_text";
+
+const DEFAULT_EXPIRY: TimeDelta = TimeDelta::hours(1);
+/// Longest a message can be shown for, so a mistyped expiry doesn't leave it up for good.
+const MAX_EXPIRY: TimeDelta = TimeDelta::days(30);
+const MAX_TEXT_LEN: usize = 500;
+const MAX_TITLE_LEN: usize = 60;
+
+/// Space the text has on the panel, in pixels, once padding and the title are taken off.
+const TEXT_WIDTH: u32 = Inky::WIDTH as u32 - 64;
+const TEXT_HEIGHT: u32 = Inky::HEIGHT as u32 - 120;
+
+/// Colour of the title bar, or of the border when there is no title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Accent {
+    #[default]
+    Black,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+impl Accent {
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Accent::Black => "black",
+            Accent::Red => "red",
+            Accent::Green => "green",
+            Accent::Blue => "blue",
+            Accent::Yellow => "yellow",
+        }
+    }
+}
+
+/// Size of the message text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextSize {
+    Tiny,
+    Small,
+    Medium,
+    Large,
+    Huge,
+}
+
+impl TextSize {
+    /// Largest first.
+    const ALL: [TextSize; 5] = [
+        TextSize::Huge,
+        TextSize::Large,
+        TextSize::Medium,
+        TextSize::Small,
+        TextSize::Tiny,
+    ];
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextSize::Tiny => "tiny",
+            TextSize::Small => "small",
+            TextSize::Medium => "medium",
+            TextSize::Large => "large",
+            TextSize::Huge => "huge",
+        }
+    }
+
+    /// Font size in pixels, matching the classes the page uses for each size.
+    fn pixels(self) -> u32 {
+        match self {
+            TextSize::Tiny => 24,
+            TextSize::Small => 36,
+            TextSize::Medium => 60,
+            TextSize::Large => 96,
+            TextSize::Huge => 192,
+        }
+    }
+
+    /// The largest size the text fits on the panel at, wrapping at spaces.
+    fn fitting(text: &str, has_title: bool) -> Self {
+        let height = if has_title {
+            TEXT_HEIGHT
+        } else {
+            TEXT_HEIGHT + 60
+        };
+        Self::ALL
+            .into_iter()
+            .find(|size| {
+                let px = size.pixels();
+                // the font is monospaced, each character around 0.55em wide
+                let per_line = (TEXT_WIDTH * 20 / (px * 11)) as usize;
+                let lines = wrapped_lines(text, per_line);
+                lines.is_some_and(|lines| lines * px * 11 / 10 <= height)
+            })
+            .unwrap_or(TextSize::Tiny)
+    }
+}
+
+/// How many lines the text wraps to with `per_line` characters a line, or `None` if a word is
+/// too long for a line.
+fn wrapped_lines(text: &str, per_line: usize) -> Option<u32> {
+    let mut lines = 0;
+    for paragraph in text.lines() {
+        lines += 1;
+        let mut used = 0;
+        for word in paragraph.split_whitespace() {
+            let len = word.chars().count();
+            if len > per_line {
+                return None;
+            }
+            if used == 0 {
+                used = len;
+            } else if used + 1 + len <= per_line {
+                used += 1 + len;
+            } else {
+                lines += 1;
+                used = len;
+            }
+        }
+    }
+    Some(lines)
+}
+
+/// A message posted to the frame.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MessageRequest {
+    pub text: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub accent: Accent,
+    /// Fitted to the panel when not given.
+    #[serde(default)]
+    pub size: Option<TextSize>,
+    /// How long to show it for, e.g. `30m`. Defaults to an hour.
+    #[serde(default)]
+    pub expires_in: Option<String>,
+    /// When to stop showing it, instead of `expires_in`.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Message {
+    pub text: String,
+    pub title: Option<String>,
+    pub accent: Accent,
+    pub size: TextSize,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl MessageRequest {
+    fn into_message(self, now: DateTime<Utc>) -> Result<Message, AppError> {
+        let invalid = |message: String| AppError::InvalidInput(message.into());
+        let text = self.text.trim().to_string();
+        if text.is_empty() {
+            return Err(invalid("Message text must not be empty".to_string()));
+        }
+        if text.chars().count() > MAX_TEXT_LEN {
+            return Err(invalid(format!(
+                "Message text can be at most {MAX_TEXT_LEN} characters"
+            )));
+        }
+        let title = self
+            .title
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty());
+        if title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_TITLE_LEN)
+        {
+            return Err(invalid(format!(
+                "Message title can be at most {MAX_TITLE_LEN} characters"
+            )));
+        }
+
+        let expires_at = match (self.expires_in, self.expires_at) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    "Set at most one of expires_in and expires_at".to_string(),
+                ));
+            }
+            (Some(expires_in), None) => {
+                let duration = parse_duration(&expires_in).map_err(|e| invalid(e.to_string()))?;
+                TimeDelta::from_std(duration)
+                    .ok()
+                    .and_then(|d| now.checked_add_signed(d))
+                    .ok_or_else(|| invalid(format!("Message expiry '{expires_in}' is too long")))?
+            }
+            (None, Some(at)) => at,
+            (None, None) => now + DEFAULT_EXPIRY,
+        };
+        if expires_at <= now {
+            return Err(invalid(format!("Message expiry {expires_at} has passed")));
+        }
+        if expires_at > now + MAX_EXPIRY {
+            return Err(invalid(format!(
+                "Messages can be shown for at most {} days",
+                MAX_EXPIRY.num_days()
+            )));
+        }
+
+        Ok(Message {
+            size: self
+                .size
+                .unwrap_or_else(|| TextSize::fitting(&text, title.is_some())),
+            text,
+            title,
+            accent: self.accent,
+            expires_at,
+        })
+    }
+}
+
+/// The message currently on show, shared between the http handlers and the scheduler.
+#[derive(Debug, Clone, Default)]
+pub struct MessageBoard {
+    current: Arc<Mutex<Option<Message>>>,
+}
+
+impl MessageBoard {
+    /// The message, unless it has expired.
+    #[must_use]
+    pub fn current(&self, now: DateTime<Utc>) -> Option<Message> {
+        let mut current = self.current.lock().expect("mutex poisoned");
+        if current.as_ref().is_some_and(|m| m.expires_at <= now) {
+            *current = None;
+        }
+        current.clone()
+    }
+
+    fn set(&self, message: Option<Message>) {
+        *self.current.lock().expect("mutex poisoned") = message;
+    }
+}
+
+/// Shows the message page now, or has the scheduler do it so it knows to go back to the
+/// schedule when the message expires.
+async fn refresh(state: &ServerAppState) -> Result<(), AppError> {
+    if let Some(scheduler) = &state.scheduler {
+        scheduler.skip();
+    } else {
+        comm::display_page(state, PAGE).await?;
+    }
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn post_message(
+    State(state): State<ServerAppState>,
+    Json(request): Json<MessageRequest>,
+) -> Result<(StatusCode, Json<Message>), AppError> {
+    let message = request.into_message(Utc::now())?;
+    tracing::info!(
+        "Showing message until {}: '{}'",
+        message.expires_at,
+        message.text
+    );
+    state.message.set(Some(message.clone()));
+    refresh(&state).await?;
+    Ok((StatusCode::CREATED, Json(message)))
+}
+
+#[debug_handler]
+pub async fn get_message(State(state): State<ServerAppState>) -> Result<Json<Message>, AppError> {
+    state
+        .message
+        .current(Utc::now())
+        .map(Json)
+        .ok_or(AppError::NotFound)
+}
+
+/// Takes the message down early, going back to the schedule.
+#[debug_handler]
+pub async fn delete_message(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
+    if state.message.current(Utc::now()).is_none() {
+        return Err(AppError::NotFound);
+    }
+    state.message.set(None);
+    if let Some(scheduler) = &state.scheduler {
+        scheduler.skip();
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(json: serde_json::Value) -> MessageRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn fits_text_to_the_panel() {
+        assert_eq!(TextSize::fitting("Lunch!", false), TextSize::Huge);
+        assert_eq!(
+            TextSize::fitting("Standup moved to 10:30", true),
+            TextSize::Large
+        );
+        assert_eq!(
+            TextSize::fitting(
+                &"The quick brown fox jumps over the lazy dog. ".repeat(6),
+                true
+            ),
+            TextSize::Small
+        );
+        // a word too long for a line at any size still gets the smallest
+        assert_eq!(TextSize::fitting(&"a".repeat(200), false), TextSize::Tiny);
+    }
+
+    #[test]
+    fn expires_messages() {
+        let now: DateTime<Utc> = "2025-07-10T09:00:00Z".parse().unwrap();
+        let message = request(serde_json::json!({
+            "text": "  Standup moved to 10:30 ",
+            "title": "",
+            "accent": "red",
+            "expires_in": "90m",
+        }))
+        .into_message(now)
+        .unwrap();
+        assert_eq!(message.text, "Standup moved to 10:30");
+        assert_eq!(message.title, None);
+        assert_eq!(message.expires_at, now + TimeDelta::minutes(90));
+
+        let board = MessageBoard::default();
+        board.set(Some(message));
+        assert!(board.current(now + TimeDelta::minutes(89)).is_some());
+        assert!(board.current(now + TimeDelta::minutes(90)).is_none());
+
+        assert!(
+            request(serde_json::json!({ "text": "Hi", "expires_at": "2025-07-10T08:00:00Z" }))
+                .into_message(now)
+                .is_err()
+        );
+        for expiry in [
+            serde_json::json!({ "expires_in": "1000000000d" }),
+            serde_json::json!({ "expires_in": "31d" }),
+            serde_json::json!({ "expires_at": "2025-08-10T09:00:00Z" }),
+            serde_json::json!({ "expires_at": "+262000-01-01T00:00:00Z" }),
+        ] {
+            let mut json = expiry.clone();
+            json["text"] = "Hi".into();
+            assert!(
+                matches!(
+                    request(json).into_message(now),
+                    Err(AppError::InvalidInput(_))
+                ),
+                "{expiry}"
+            );
+        }
+        assert!(
+            request(serde_json::json!({ "text": "Hi", "expires_in": "30d" }))
+                .into_message(now)
+                .is_ok()
+        );
+        assert!(
+            request(serde_json::json!({ "text": " " }))
+                .into_message(now)
+                .is_err()
+        );
+    }
+}
@@ -1,72 +1,227 @@
-use crate::AppError;
-use crate::data_sources;
+use crate::dashboard::{Layout, Widget};
+use crate::data_sources::{
+    self, AirQualitySource, ArrivalsSource, BikesSource, CalendarSource, ClosuresSource,
+    DataSource, Fetched, FootballSource, HeadlinesSource, RailSource, StandingsSource, TubeSource,
+    WeatherSource,
+};
+use crate::message::Message;
+use crate::{AppError, ServerAppState, chart};
 use askama::Template;
 use axum::extract::State;
 use axum::response::Html;
-use reqwest::Client;
+use chrono::Utc;
 
-pub async fn large_text_handler() -> Result<Html<String>, AppError> {
+pub async fn large_text_handler(
+    State(state): State<ServerAppState>,
+) -> Result<Html<String>, AppError> {
     #[derive(Debug, Template)]
     #[template(path = "large_text.html")]
     struct Tmpl {
-        words: String,
+        message: Option<Message>,
     }
 
     let template = Tmpl {
-        words: "Hello there".to_string(),
+        message: state.message.current(Utc::now()),
     };
     Ok(Html(template.render()?))
 }
 
-pub async fn dashboard_handler(State(client): State<Client>) -> Result<Html<String>, AppError> {
+pub async fn dashboard_handler(
+    State(state): State<ServerAppState>,
+) -> Result<Html<String>, AppError> {
     #[derive(Debug, Template)]
     #[template(path = "dashboard.html")]
     struct DashboardTmpl {
-        tube_lines: Vec<data_sources::LineStatus>,
+        tube_lines: Option<Result<Fetched<Vec<data_sources::LineStatus>>, String>>,
         has_disruptions: bool,
-        weather: data_sources::Weather,
-        matches: Vec<data_sources::Match>,
+        weekend: Option<Result<Fetched<data_sources::WeekendClosures>, String>>,
+        rail: Option<Result<Fetched<data_sources::RailDepartures>, String>>,
+        bikes: Option<Result<Fetched<Vec<data_sources::DockStatus>>, String>>,
+        agenda: Option<Result<Fetched<data_sources::Agenda>, String>>,
+        weather: Option<Result<Fetched<data_sources::Weather>, String>>,
+        /// SVG of the next hours' rain and temperature.
+        weather_chart: Option<String>,
+        air_quality: Option<Result<Fetched<data_sources::AirQuality>, String>>,
+        fixtures: Option<Result<Fetched<data_sources::Fixtures>, String>>,
+        standings: Option<Result<Fetched<data_sources::Standings>, String>>,
+        headlines: Option<Result<Fetched<Vec<data_sources::Headline>>, String>>,
+        layout: Layout,
     }
 
-    let (tube_result, weather_result, arsenal_result) = tokio::join!(
-        data_sources::fetch_tube_status(&client),
-        data_sources::fetch_weather(&client),
-        data_sources::fetch_arsenal_matches(&client),
-    );
+    // only what the layout shows, so the rest stay None
+    let dashboard = &state.config.dashboard;
+    let data = state
+        .config
+        .sources
+        .fetch(&state.client, &dashboard.sources())
+        .await;
+    let tube_lines = data.get_cloned::<TubeSource>();
 
-    let tube_lines = tube_result.unwrap_or_else(|e| {
-        tracing::error!("Failed to fetch TfL status: {}", e);
-        Vec::new()
+    // Only show the disrupted lines when there are any
+    let has_disruptions = tube_lines
+        .as_ref()
+        .and_then(|r| r.as_ref().ok())
+        .is_some_and(|t| t.value.iter().any(|l| !l.is_good_service()));
+    let tube_lines = tube_lines.map(|r| {
+        r.map(|t| {
+            t.map(|lines| {
+                if has_disruptions {
+                    lines.into_iter().filter(|l| !l.is_good_service()).collect()
+                } else {
+                    lines
+                }
+            })
+        })
     });
 
-    // Filter to only show lines with disruptions
-    let disrupted_lines: Vec<data_sources::LineStatus> = tube_lines
-        .iter()
-        .filter(|l| l.status != "Good Service")
-        .cloned()
-        .collect();
+    let weather = data.get_cloned::<WeatherSource>();
+    let weather_chart = weather
+        .as_ref()
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|w| chart::hourly_forecast(&w.value.hourly, w.value.units, 328, 80));
 
-    let has_disruptions = !disrupted_lines.is_empty();
+    let weekend = data_sources::weekend_is_near(state.config.localization.today())
+        .then(|| data.get_cloned::<ClosuresSource>())
+        .flatten();
+    let rail = data.get_cloned::<RailSource>();
+    let bikes = data.get_cloned::<BikesSource>();
+    let agenda = data.get_cloned::<CalendarSource>();
+    let air_quality = data.get_cloned::<AirQualitySource>();
+    let fixtures = data.get_cloned::<FootballSource>();
+    let standings = data.get_cloned::<StandingsSource>();
+    let headlines = data.get_cloned::<HeadlinesSource>();
 
-    let weather = weather_result.unwrap_or_else(|e| {
-        tracing::error!("Failed to fetch weather: {}", e);
-        data_sources::Weather::default()
-    });
-
-    let matches = arsenal_result.unwrap_or_else(|e| {
-        tracing::error!("Failed to fetch Arsenal matches: {}", e);
-        Vec::new()
+    // collapse regions that failed or came back empty, not just those whose source is off
+    let layout = dashboard.layout(|widget| match widget {
+        Widget::Tube => shows(&tube_lines, Vec::is_empty) || shows(&weekend, |_| false),
+        Widget::Rail => shows(&rail, |r| r.services.is_empty()),
+        Widget::Bikes => shows(&bikes, Vec::is_empty),
+        Widget::Agenda => shows(&agenda, |a| a.days.is_empty()),
+        Widget::Weather => shows(&weather, |_| false),
+        Widget::AirQuality => shows(&air_quality, |_| false),
+        Widget::Fixtures => shows(&fixtures, |f| f.matches.is_empty()),
+        Widget::Standings => shows(&standings, |s| s.rows.is_empty()),
+        Widget::Headlines => shows(&headlines, Vec::is_empty),
     });
 
     let template = DashboardTmpl {
-        tube_lines: if has_disruptions {
-            disrupted_lines
-        } else {
-            tube_lines
-        },
+        tube_lines,
         has_disruptions,
+        weekend,
+        rail,
+        bikes,
+        agenda,
         weather,
-        matches,
+        weather_chart,
+        air_quality,
+        fixtures,
+        standings,
+        headlines,
+        layout,
+    };
+
+    Ok(Html(template.render()?))
+}
+
+/// Whether a source was fetched and has something to show.
+fn shows<T>(fetched: &Option<Result<Fetched<T>, String>>, is_empty: impl Fn(&T) -> bool) -> bool {
+    matches!(fetched, Some(Ok(fetched)) if !is_empty(&fetched.value))
+}
+
+pub async fn matchday_handler(
+    State(state): State<ServerAppState>,
+) -> Result<Html<String>, AppError> {
+    #[derive(Debug, Template)]
+    #[template(path = "matchday.html")]
+    struct MatchdayTmpl {
+        fixtures: Option<Result<Fetched<data_sources::Fixtures>, String>>,
+        featured: Option<data_sources::Match>,
+        standings: Option<Result<Fetched<data_sources::Standings>, String>>,
+    }
+
+    const SOURCES: &[&str] = &[FootballSource::NAME, StandingsSource::NAME];
+
+    let data = state.config.sources.fetch(&state.client, SOURCES).await;
+    let fixtures = data.get_cloned::<FootballSource>();
+    let featured = fixtures
+        .as_ref()
+        .and_then(|r| r.as_ref().ok())
+        .and_then(|f| f.value.featured(Utc::now()).cloned());
+
+    let template = MatchdayTmpl {
+        fixtures,
+        featured,
+        standings: data.get_cloned::<StandingsSource>(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn departures_handler(
+    State(state): State<ServerAppState>,
+) -> Result<Html<String>, AppError> {
+    #[derive(Debug, Template)]
+    #[template(path = "departures.html")]
+    struct DeparturesTmpl {
+        stops: Option<Result<Fetched<Vec<data_sources::StopDepartures>>, String>>,
+        now: String,
+    }
+
+    let data = state
+        .config
+        .sources
+        .fetch(&state.client, &[ArrivalsSource::NAME])
+        .await;
+
+    let template = DeparturesTmpl {
+        stops: data.get_cloned::<ArrivalsSource>(),
+        now: state.config.localization.now().format("%H:%M").to_string(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn agenda_handler(State(state): State<ServerAppState>) -> Result<Html<String>, AppError> {
+    #[derive(Debug, Template)]
+    #[template(path = "agenda.html")]
+    struct AgendaTmpl {
+        agenda: Option<Result<Fetched<data_sources::Agenda>, String>>,
+        now: String,
+    }
+
+    let data = state
+        .config
+        .sources
+        .fetch(&state.client, &[CalendarSource::NAME])
+        .await;
+
+    let template = AgendaTmpl {
+        agenda: data.get_cloned::<CalendarSource>(),
+        now: state.config.localization.now().format("%H:%M").to_string(),
+    };
+
+    Ok(Html(template.render()?))
+}
+
+pub async fn headlines_handler(
+    State(state): State<ServerAppState>,
+) -> Result<Html<String>, AppError> {
+    #[derive(Debug, Template)]
+    #[template(path = "headlines.html")]
+    struct HeadlinesTmpl {
+        headlines: Option<Result<Fetched<Vec<data_sources::Headline>>, String>>,
+        now: String,
+    }
+
+    let data = state
+        .config
+        .sources
+        .fetch(&state.client, &[HeadlinesSource::NAME])
+        .await;
+
+    let template = HeadlinesTmpl {
+        headlines: data.get_cloned::<HeadlinesSource>(),
+        now: state.config.localization.now().format("%H:%M").to_string(),
     };
 
     Ok(Html(template.render()?))
@@ -0,0 +1,490 @@
+use crate::AppError;
+use crate::controller::Inky;
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Extensions of the images that can be decoded.
+pub(crate) const EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Extension of the optional settings file next to an image, e.g. `beach.jpg.toml`.
+const SIDECAR_EXTENSION: &str = "toml";
+
+fn default_dir() -> PathBuf {
+    PathBuf::from("./images")
+}
+
+fn default_dither() -> bool {
+    true
+}
+
+fn default_max_upload_mb() -> u64 {
+    20
+}
+
+/// Order the photo frame goes through the images in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Shuffle,
+    /// By path, so folders can be used to group images.
+    Sequential,
+    /// Photos taken on today's date in earlier years, by their EXIF date. Falls back to
+    /// shuffling every image on days without any.
+    OnThisDay,
+}
+
+/// How an image that isn't the shape of the panel is made to fit it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fit {
+    /// Scaled to fit inside the panel, with white bars at the sides.
+    #[default]
+    Contain,
+    /// Scaled to fill the panel, cropping the edges.
+    Cover,
+    /// Scaled to the panel's size, ignoring the aspect ratio.
+    Stretch,
+}
+
+impl Fit {
+    #[must_use]
+    pub fn apply(self, image: &DynamicImage) -> image::RgbImage {
+        let (width, height) = (Inky::WIDTH as u32, Inky::HEIGHT as u32);
+        if image.width() == width && image.height() == height {
+            return image.to_rgb8();
+        }
+        match self {
+            Fit::Contain => image.resize(width, height, FilterType::Lanczos3),
+            Fit::Cover => image.resize_to_fill(width, height, FilterType::Lanczos3),
+            Fit::Stretch => image.resize_exact(width, height, FilterType::Lanczos3),
+        }
+        .to_rgb8()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhotosConfig {
+    /// Searched recursively, skipping hidden files and folders.
+    #[serde(default = "default_dir")]
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub order: Order,
+    /// Defaults for images without a sidecar file saying otherwise.
+    #[serde(default)]
+    pub fit: Fit,
+    #[serde(default = "default_dither")]
+    pub dither: bool,
+    /// From 0 to 1, defaults to the frame's own.
+    #[serde(default)]
+    pub saturation: Option<f32>,
+    /// Largest image that can be uploaded, in megabytes.
+    #[serde(default = "default_max_upload_mb")]
+    pub max_upload_mb: u64,
+}
+
+impl Default for PhotosConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_dir(),
+            order: Order::default(),
+            fit: Fit::default(),
+            dither: default_dither(),
+            saturation: None,
+            max_upload_mb: default_max_upload_mb(),
+        }
+    }
+}
+
+impl PhotosConfig {
+    /// Returns a description of each invalid setting.
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<_> = self
+            .settings()
+            .validate()
+            .err()
+            .map(|e| e.to_string())
+            .into_iter()
+            .collect();
+        if self.max_upload_mb == 0 {
+            problems.push("max_upload_mb must be at least 1".to_string());
+        }
+        problems
+    }
+
+    #[must_use]
+    pub fn max_upload_bytes(&self) -> usize {
+        usize::try_from(self.max_upload_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX)
+    }
+
+    fn settings(&self) -> ImageSettings {
+        ImageSettings {
+            fit: self.fit,
+            dither: self.dither,
+            saturation: self.saturation,
+        }
+    }
+}
+
+/// How to show one image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageSettings {
+    pub fit: Fit,
+    pub dither: bool,
+    pub saturation: Option<f32>,
+}
+
+impl ImageSettings {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.saturation.is_some_and(|s| !(0.0..=1.0).contains(&s)) {
+            anyhow::bail!("saturation must be between 0 and 1");
+        }
+        Ok(())
+    }
+}
+
+/// Settings for a single image, overriding the `[photos]` ones.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sidecar {
+    fit: Option<Fit>,
+    dither: Option<bool>,
+    saturation: Option<f32>,
+}
+
+/// An image to show and how to show it.
+#[derive(Debug, Clone)]
+pub struct Photo {
+    pub path: PathBuf,
+    pub settings: ImageSettings,
+}
+
+impl Photo {
+    /// Decodes the image, turning it the right way up if its EXIF says it was taken rotated.
+    pub fn decode(&self) -> Result<DynamicImage, AppError> {
+        let reader = image::ImageReader::open(&self.path).map_err(|e| {
+            tracing::info!(
+                "Error reading image file at '{}': '{}'",
+                self.path.display(),
+                e
+            );
+            AppError::InvalidInput("Unable to find image".into())
+        })?;
+        let mut decoder = reader
+            .with_guessed_format()?
+            .into_decoder()
+            .context("Couldn't decode image")?;
+        let orientation = decoder.orientation().context("Couldn't decode image")?;
+        let mut image = DynamicImage::from_decoder(decoder).context("Couldn't decode image")?;
+        image.apply_orientation(orientation);
+        Ok(image)
+    }
+}
+
+/// Which images are still to be shown this time round.
+#[derive(Debug, Default)]
+struct Rotation {
+    queue: VecDeque<PathBuf>,
+    /// Day the queue was made, as the photos taken on this day change at midnight.
+    made_on: Option<NaiveDate>,
+    last: Option<PathBuf>,
+    /// EXIF dates already read, `None` for images without one.
+    taken: HashMap<PathBuf, Option<NaiveDate>>,
+}
+
+/// Goes through every image in the photos directory before showing any of them again.
+#[derive(Debug)]
+pub struct PhotoFrame {
+    config: PhotosConfig,
+    rotation: Mutex<Rotation>,
+}
+
+impl PhotoFrame {
+    #[must_use]
+    pub fn new(config: PhotosConfig) -> Self {
+        Self {
+            config,
+            rotation: Mutex::new(Rotation::default()),
+        }
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &PhotosConfig {
+        &self.config
+    }
+
+    /// Path of the image called `name`, a path relative to the photos directory such as
+    /// `holiday/beach.jpg`. Names that could reach outside the directory are rejected.
+    pub fn resolve(&self, name: &str) -> Result<PathBuf, AppError> {
+        let unsafe_part =
+            |part: &str| part.is_empty() || part.starts_with('.') || part.contains(['\\', '\0']);
+        if name.split('/').any(unsafe_part) {
+            return Err(AppError::InvalidInput(
+                format!("Invalid image name: '{name}'").into(),
+            ));
+        }
+        Ok(self.config.dir.join(name))
+    }
+
+    /// The image called `name` in the photos directory.
+    pub fn photo(&self, name: &str) -> Result<Photo, AppError> {
+        let path = self.resolve(name)?;
+        let settings = self.settings(&path);
+        Ok(Photo { path, settings })
+    }
+
+    /// Picks the next image, scanning the directory again once every image has been shown.
+    /// Blocks on the filesystem.
+    pub fn next(&self, today: NaiveDate) -> anyhow::Result<Photo> {
+        let mut rotation = self.rotation.lock().expect("mutex poisoned");
+        let stale = self.config.order == Order::OnThisDay && rotation.made_on != Some(today);
+        if stale {
+            rotation.queue.clear();
+        }
+
+        loop {
+            if rotation.queue.is_empty() {
+                self.refill(&mut rotation, today)?;
+            }
+            let Some(path) = rotation.queue.pop_front() else {
+                anyhow::bail!("no images in '{}'", self.config.dir.display());
+            };
+            // removed since the scan
+            if !path.is_file() {
+                continue;
+            }
+            rotation.last = Some(path.clone());
+            let settings = self.settings(&path);
+            return Ok(Photo { path, settings });
+        }
+    }
+
+    fn refill(&self, rotation: &mut Rotation, today: NaiveDate) -> anyhow::Result<()> {
+        let mut images = Vec::new();
+        scan(&self.config.dir, &mut images)
+            .with_context(|| format!("Couldn't read '{}'", self.config.dir.display()))?;
+
+        if self.config.order == Order::OnThisDay {
+            for image in &images {
+                if !rotation.taken.contains_key(image) {
+                    rotation.taken.insert(image.clone(), taken_on(image));
+                }
+            }
+        }
+        let mut queue = make_queue(self.config.order, images, today, |image| {
+            rotation.taken.get(image).copied().flatten()
+        });
+
+        // don't show the last image of one round first in the next
+        if queue.len() > 1 && queue.front() == rotation.last.as_ref() {
+            queue.rotate_left(1);
+        }
+        tracing::info!("Photo frame has {} image(s) to show", queue.len());
+        rotation.queue = queue;
+        rotation.made_on = Some(today);
+        Ok(())
+    }
+
+    /// The `[photos]` settings with any from the image's sidecar file on top.
+    fn settings(&self, path: &Path) -> ImageSettings {
+        let defaults = self.config.settings();
+        let sidecar_path = sidecar_path(path);
+
+        let sidecar = match std::fs::read_to_string(&sidecar_path) {
+            Ok(contents) => toml::from_str::<Sidecar>(&contents)
+                .map_err(anyhow::Error::from)
+                .map(|sidecar| ImageSettings {
+                    fit: sidecar.fit.unwrap_or(defaults.fit),
+                    dither: sidecar.dither.unwrap_or(defaults.dither),
+                    saturation: sidecar.saturation.or(defaults.saturation),
+                })
+                .and_then(|settings| settings.validate().map(|()| settings)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return defaults,
+            Err(e) => Err(e.into()),
+        };
+        sidecar.unwrap_or_else(|e| {
+            tracing::warn!("Ignoring '{}': {e}", sidecar_path.display());
+            defaults
+        })
+    }
+}
+
+/// Where the settings for the image at `path` are kept.
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(format!(".{SIDECAR_EXTENSION}"));
+    PathBuf::from(sidecar)
+}
+
+/// Adds every image under `dir` to `images`.
+pub(crate) fn scan(dir: &Path, images: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            scan(&path, images)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        {
+            images.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Date the photo was taken, from its EXIF data.
+pub(crate) fn taken_on(path: &Path) -> Option<NaiveDate> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return None;
+    };
+    let taken = exif::DateTime::from_ascii(values.first()?).ok()?;
+    NaiveDate::from_ymd_opt(taken.year.into(), taken.month.into(), taken.day.into())
+}
+
+fn make_queue(
+    order: Order,
+    mut images: Vec<PathBuf>,
+    today: NaiveDate,
+    taken: impl Fn(&Path) -> Option<NaiveDate>,
+) -> VecDeque<PathBuf> {
+    match order {
+        Order::Sequential => images.sort(),
+        Order::Shuffle => images.shuffle(&mut rand::rng()),
+        Order::OnThisDay => {
+            let on_this_day: Vec<_> = images
+                .iter()
+                .filter(|image| {
+                    taken(image).is_some_and(|date| {
+                        (date.month(), date.day()) == (today.month(), today.day())
+                            && date.year() < today.year()
+                    })
+                })
+                .cloned()
+                .collect();
+            if on_this_day.is_empty() {
+                tracing::info!("No photos taken on this day, showing them all");
+            } else {
+                images = on_this_day;
+            }
+            images.shuffle(&mut rand::rng());
+        }
+    }
+    images.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("inky_display_photos_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (file, contents) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn caps_upload_limit() {
+        let config = |max_upload_mb| PhotosConfig {
+            max_upload_mb,
+            ..PhotosConfig::default()
+        };
+        assert_eq!(config(2).max_upload_bytes(), 2 * 1024 * 1024);
+        assert_eq!(config(u64::MAX).max_upload_bytes(), usize::MAX);
+    }
+
+    #[test]
+    fn shows_every_image_before_repeating() {
+        let dir = photo_dir(
+            "rotation",
+            &[
+                ("b.png", ""),
+                ("a.JPG", ""),
+                ("holiday/c.jpg", ""),
+                ("holiday/c.jpg.toml", "fit = \"cover\"\nsaturation = 0.2"),
+                ("notes.txt", ""),
+                (".hidden/d.png", ""),
+            ],
+        );
+        let frame = PhotoFrame::new(PhotosConfig {
+            dir: dir.clone(),
+            order: Order::Sequential,
+            ..PhotosConfig::default()
+        });
+        let today = NaiveDate::from_ymd_opt(2025, 7, 10).unwrap();
+
+        let shown: Vec<_> = (0..4)
+            .map(|_| {
+                let photo = frame.next(today).unwrap();
+                let name = photo.path.strip_prefix(&dir).unwrap().to_path_buf();
+                (name, photo.settings.fit, photo.settings.saturation)
+            })
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                (PathBuf::from("a.JPG"), Fit::Contain, None),
+                (PathBuf::from("b.png"), Fit::Contain, None),
+                (PathBuf::from("holiday/c.jpg"), Fit::Cover, Some(0.2)),
+                (PathBuf::from("a.JPG"), Fit::Contain, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_photos_taken_on_this_day() {
+        let images: Vec<_> = [
+            "2019.jpg",
+            "2023.jpg",
+            "other.jpg",
+            "undated.jpg",
+            "today.jpg",
+        ]
+        .map(PathBuf::from)
+        .into();
+        let taken = |image: &Path| match image.to_str().unwrap() {
+            "2019.jpg" => NaiveDate::from_ymd_opt(2019, 7, 10),
+            "2023.jpg" => NaiveDate::from_ymd_opt(2023, 7, 10),
+            "other.jpg" => NaiveDate::from_ymd_opt(2023, 7, 11),
+            "today.jpg" => NaiveDate::from_ymd_opt(2025, 7, 10),
+            _ => None,
+        };
+
+        let today = NaiveDate::from_ymd_opt(2025, 7, 10).unwrap();
+        let mut queue: Vec<_> = make_queue(Order::OnThisDay, images.clone(), today, taken).into();
+        queue.sort();
+        assert_eq!(
+            queue,
+            [PathBuf::from("2019.jpg"), PathBuf::from("2023.jpg")]
+        );
+
+        let no_photos = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        assert_eq!(
+            make_queue(Order::OnThisDay, images, no_photos, taken).len(),
+            5
+        );
+    }
+}
@@ -0,0 +1,599 @@
+use crate::comm::{self, RenderUrlRequest};
+use crate::config::deserialize_duration;
+use crate::data_sources::{DataSource, Fixtures, FootballSource};
+use crate::{AppError, ServerAppState, message};
+use anyhow::{Context, anyhow};
+use axum::Json;
+use axum::debug_handler;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How long to wait before looking again when nothing in the playlist is currently eligible.
+const IDLE_POLL: Duration = Duration::from_secs(60);
+
+/// Something that can be shown on the frame.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Target {
+    Page {
+        name: String,
+    },
+    Image {
+        name: String,
+    },
+    /// The photo frame's next image.
+    Photo,
+    Url(RenderUrlRequest),
+}
+
+impl Target {
+    async fn display(&self, state: &ServerAppState) -> Result<StatusCode, AppError> {
+        match self {
+            Target::Page { name } => comm::display_page(state, name).await,
+            Target::Image { name } => comm::display_image(state, name).await,
+            Target::Photo => comm::display_photo(state).await,
+            Target::Url(request) => comm::display_url(state, request.clone()).await,
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Page { name } => write!(f, "page:{name}"),
+            Target::Image { name } => write!(f, "image:{name}"),
+            Target::Photo => write!(f, "photo"),
+            Target::Url(request) => write!(f, "url:{}", request.url),
+        }
+    }
+}
+
+/// A time-of-day window such as `07:00-23:00`. Windows that end before they start wrap past
+/// midnight, so `23:00-07:00` covers the night.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    #[must_use]
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Expected a window like '07:00-23:00', got: '{s}'"))?;
+        let parse = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time '{t}' in window '{s}'"))
+        };
+
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+fn deserialize_window<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<TimeWindow>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistEntry {
+    #[serde(flatten)]
+    pub target: Target,
+    /// How long the entry stays up before moving on. Shown again after this if it is the only
+    /// eligible entry, which is how pages get refreshed.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub duration: Duration,
+    /// Only show the entry during this time of day.
+    #[serde(default, deserialize_with = "deserialize_window")]
+    pub window: Option<TimeWindow>,
+}
+
+impl PlaylistEntry {
+    fn is_active(&self, time: NaiveTime) -> bool {
+        self.window.is_none_or(|w| w.contains(time))
+    }
+}
+
+/// Shows a target at times matching a cron expression, interrupting the playlist.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// Cron expression with seconds, e.g. `0 0 8 * * Mon-Fri`.
+    pub cron: String,
+    #[serde(flatten)]
+    pub target: Target,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub duration: Duration,
+}
+
+fn default_state_path() -> PathBuf {
+    PathBuf::from("./scheduler_state.json")
+}
+
+fn default_match_day_page() -> String {
+    "matchday".to_string()
+}
+
+fn default_match_day_refresh() -> Duration {
+    Duration::from_secs(2 * 60)
+}
+
+fn default_max_refreshes_per_hour() -> u32 {
+    20
+}
+
+/// Takes over the display while a followed team is playing, refreshing it more often than the
+/// playlist would.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchDayConfig {
+    /// Page shown for the duration of the match.
+    #[serde(default = "default_match_day_page")]
+    pub page: String,
+    /// How often to refresh the page during the match.
+    #[serde(
+        default = "default_match_day_refresh",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub refresh: Duration,
+    /// Most panel refreshes allowed in any hour during the match, as each one takes the frame
+    /// around half a minute.
+    #[serde(default = "default_max_refreshes_per_hour")]
+    pub max_refreshes_per_hour: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    /// Where the scheduler keeps its position so it survives restarts.
+    #[serde(default = "default_state_path")]
+    pub state_path: PathBuf,
+    #[serde(default)]
+    pub playlist: Vec<PlaylistEntry>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Switch to a live match page while a followed team is playing.
+    #[serde(default)]
+    pub match_day: Option<MatchDayConfig>,
+}
+
+impl ScheduleConfig {
+    /// Returns a description of each invalid setting.
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<_> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                cron::Schedule::from_str(&rule.cron)
+                    .err()
+                    .map(|e| format!("invalid cron expression '{}': {e}", rule.cron))
+            })
+            .collect();
+        if self
+            .match_day
+            .as_ref()
+            .is_some_and(|m| m.max_refreshes_per_hour == 0)
+        {
+            problems.push("match_day.max_refreshes_per_hour must be at least 1".to_string());
+        }
+        problems
+    }
+}
+
+/// State that is persisted between restarts and reported by the status endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchedulerStatus {
+    /// Position in the playlist to continue from.
+    pub index: usize,
+    pub current: Option<String>,
+    pub shown_at: Option<DateTime<Utc>>,
+    pub next_at: Option<DateTime<Utc>>,
+}
+
+impl SchedulerStatus {
+    fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents)
+            .inspect_err(|e| tracing::warn!("Ignoring unreadable scheduler state: {e}"))
+            .ok()
+    }
+
+    async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+/// Handle for talking to a running scheduler from the http handlers.
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerHandle {
+    skip: Arc<Notify>,
+    status: Arc<Mutex<SchedulerStatus>>,
+}
+
+impl SchedulerHandle {
+    pub fn skip(&self) {
+        self.skip.notify_one();
+    }
+
+    #[must_use]
+    pub fn status(&self) -> SchedulerStatus {
+        self.status.lock().expect("mutex poisoned").clone()
+    }
+}
+
+/// Recent panel refreshes, so match-day mode can keep within its hourly budget.
+#[derive(Debug, Default)]
+struct RefreshBudget {
+    shown: VecDeque<DateTime<Utc>>,
+}
+
+impl RefreshBudget {
+    fn record(&mut self, at: DateTime<Utc>) {
+        self.shown.push_back(at);
+        while self
+            .shown
+            .front()
+            .is_some_and(|&t| t <= at - TimeDelta::hours(1))
+        {
+            self.shown.pop_front();
+        }
+    }
+
+    /// The earliest time at least `interval` after `now` that another refresh keeps the last
+    /// hour within `max_per_hour`.
+    fn next_allowed(
+        &self,
+        now: DateTime<Utc>,
+        interval: Duration,
+        max_per_hour: u32,
+    ) -> DateTime<Utc> {
+        let wanted = TimeDelta::from_std(interval)
+            .ok()
+            .and_then(|d| now.checked_add_signed(d))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let max = max_per_hour as usize;
+        match self.shown.len().checked_sub(max) {
+            Some(i) if max > 0 => wanted.max(self.shown[i] + TimeDelta::hours(1)),
+            _ => wanted,
+        }
+    }
+}
+
+pub struct Scheduler {
+    config: ScheduleConfig,
+    rules: Vec<(cron::Schedule, Rule)>,
+    match_day_target: Option<Target>,
+    message_target: Target,
+    /// Timezone the time windows and cron rules are in.
+    tz: Tz,
+    handle: SchedulerHandle,
+}
+
+impl Scheduler {
+    pub fn new(config: ScheduleConfig, tz: Tz) -> anyhow::Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                cron::Schedule::from_str(&rule.cron)
+                    .with_context(|| format!("Invalid cron expression: '{}'", rule.cron))
+                    .map(|schedule| (schedule, rule.clone()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let status = SchedulerStatus::load(&config.state_path).unwrap_or_default();
+        let match_day_target = config.match_day.as_ref().map(|m| Target::Page {
+            name: m.page.clone(),
+        });
+
+        Ok(Self {
+            config,
+            rules,
+            match_day_target,
+            message_target: Target::Page {
+                name: message::PAGE.to_string(),
+            },
+            tz,
+            handle: SchedulerHandle {
+                skip: Arc::new(Notify::new()),
+                status: Arc::new(Mutex::new(status)),
+            },
+        })
+    }
+
+    #[must_use]
+    pub fn handle(&self) -> SchedulerHandle {
+        self.handle.clone()
+    }
+
+    /// Finds the first playlist entry at or after `start` that is active at the given time.
+    fn next_entry(&self, start: usize, time: NaiveTime) -> Option<usize> {
+        let len = self.config.playlist.len();
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&i| self.config.playlist[i].is_active(time))
+    }
+
+    /// Returns the rule that fired most recently in `(since, now]`, if any.
+    fn fired_rule(&self, since: DateTime<Tz>, now: DateTime<Tz>) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .filter_map(|(schedule, rule)| {
+                schedule
+                    .after(&since)
+                    .take_while(|t| *t <= now)
+                    .last()
+                    .map(|t| (t, rule))
+            })
+            .max_by_key(|(t, _)| *t)
+            .map(|(_, rule)| rule)
+    }
+
+    fn next_rule_time(&self, now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        self.rules
+            .iter()
+            .filter_map(|(schedule, _)| schedule.after(&now).next())
+            .min()
+    }
+
+    /// Current fixtures when match-day mode is enabled.
+    async fn fixtures(&self, state: &ServerAppState) -> Option<Fixtures> {
+        self.config.match_day.as_ref()?;
+        let data = state
+            .config
+            .sources
+            .fetch(&state.client, &[FootballSource::NAME])
+            .await;
+        data.get_cloned::<FootballSource>()?.ok().map(|f| f.value)
+    }
+
+    /// Waits until the deadline, returning early if a skip was requested.
+    async fn wait(&self, deadline: DateTime<Utc>) {
+        let remaining = (deadline - Utc::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            () = tokio::time::sleep(remaining) => {}
+            () = self.handle.skip.notified() => tracing::info!("Skipping to next entry"),
+        }
+    }
+
+    pub async fn run(self, state: ServerAppState) {
+        let mut status = self.handle.status();
+        let mut last_check = Utc::now().with_timezone(&self.tz);
+
+        // carry on showing whatever was up before a restart, the panel keeps its image
+        if let Some(next_at) = status.next_at
+            && next_at > Utc::now()
+        {
+            tracing::info!("Resuming schedule, next change at {next_at}");
+            self.wait(next_at).await;
+        }
+
+        let mut budget = RefreshBudget::default();
+        loop {
+            let now = Utc::now().with_timezone(&self.tz);
+            let next_rule = self.next_rule_time(now);
+            let fixtures = self.fixtures(&state).await;
+            let next_kickoff = fixtures
+                .as_ref()
+                .and_then(|f| f.next_kickoff(now.to_utc()))
+                .map(|t| t.with_timezone(&self.tz));
+            let wake = next_rule.into_iter().chain(next_kickoff).min();
+            let live = fixtures.as_ref().and_then(|f| f.in_progress(now.to_utc()));
+
+            let (target, next_at) = if let Some(message) = state.message.current(now.to_utc()) {
+                // a posted message outranks everything until it expires or is taken down
+                tracing::info!("Showing message until {}", message.expires_at);
+                (&self.message_target, message.expires_at)
+            } else if let (Some(config), Some(target), Some(live)) =
+                (&self.config.match_day, &self.match_day_target, live)
+            {
+                tracing::info!("{} v {} in progress", live.home_team, live.away_team);
+                // the cache only refreshes every half hour, too slow to follow the score
+                state
+                    .config
+                    .sources
+                    .refresh::<FootballSource>(&state.client)
+                    .await;
+                let next_at = budget.next_allowed(
+                    now.to_utc(),
+                    config.refresh,
+                    config.max_refreshes_per_hour,
+                );
+                (target, next_at)
+            } else {
+                let (target, duration) = if let Some(rule) = self.fired_rule(last_check, now) {
+                    (&rule.target, rule.duration)
+                } else if let Some(i) = self.next_entry(status.index, now.time()) {
+                    status.index = (i + 1) % self.config.playlist.len();
+                    let entry = &self.config.playlist[i];
+                    (&entry.target, entry.duration)
+                } else {
+                    last_check = now;
+                    let idle = now + TimeDelta::from_std(IDLE_POLL).expect("poll interval fits");
+                    self.wait(wake.map_or(idle, |t| t.min(idle)).to_utc()).await;
+                    continue;
+                };
+                // a duration too long to add to the date shows the target until a rule or a skip
+                let until = TimeDelta::from_std(duration)
+                    .ok()
+                    .and_then(|d| now.checked_add_signed(d));
+                let next_at = wake
+                    .into_iter()
+                    .chain(until)
+                    .min()
+                    .map_or(DateTime::<Utc>::MAX_UTC, |t| t.to_utc());
+                (target, next_at)
+            };
+            last_check = now;
+
+            tracing::info!("Scheduler showing '{target}'");
+            if let Err(e) = target.display(&state).await {
+                tracing::error!("Failed to display '{target}': {e:?}");
+            }
+            budget.record(now.to_utc());
+
+            status.current = Some(target.to_string());
+            status.shown_at = Some(now.to_utc());
+            status.next_at = Some(next_at);
+
+            *self.handle.status.lock().expect("mutex poisoned") = status.clone();
+            if let Err(e) = status.save(&self.config.state_path).await {
+                tracing::warn!("Failed to save scheduler state: {e:?}");
+            }
+
+            self.wait(next_at).await;
+        }
+    }
+}
+
+fn scheduler_handle(state: &ServerAppState) -> Result<&SchedulerHandle, AppError> {
+    state
+        .scheduler
+        .as_ref()
+        .ok_or_else(|| AppError::InvalidInput("Scheduler is not enabled".into()))
+}
+
+#[debug_handler]
+pub async fn skip(State(state): State<ServerAppState>) -> Result<StatusCode, AppError> {
+    scheduler_handle(&state)?.skip();
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[debug_handler]
+pub async fn status(
+    State(state): State<ServerAppState>,
+) -> Result<Json<SchedulerStatus>, AppError> {
+    Ok(Json(scheduler_handle(&state)?.status()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    fn scheduler(toml: &str) -> Scheduler {
+        let mut config: ScheduleConfig = toml::from_str(toml).unwrap();
+        config.state_path = PathBuf::from("/nonexistent/scheduler_state.json");
+        Scheduler::new(config, chrono_tz::Europe::London).unwrap()
+    }
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        let day: TimeWindow = "07:00-23:00".parse().unwrap();
+        assert!(day.contains(time("07:00")));
+        assert!(day.contains(time("22:59")));
+        assert!(!day.contains(time("23:00")));
+
+        let night: TimeWindow = "23:00-07:00".parse().unwrap();
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("03:00")));
+        assert!(!night.contains(time("12:00")));
+    }
+
+    #[test]
+    fn picks_entries_in_window() {
+        let s = scheduler(
+            r#"
+            [[playlist]]
+            type = "page"
+            name = "dashboard"
+            duration = "15m"
+            window = "07:00-23:00"
+
+            [[playlist]]
+            type = "image"
+            name = "night.png"
+            duration = "1h"
+            window = "23:00-07:00"
+            "#,
+        );
+
+        assert_eq!(s.next_entry(0, time("12:00")), Some(0));
+        assert_eq!(s.next_entry(1, time("12:00")), Some(0));
+        assert_eq!(s.next_entry(0, time("02:00")), Some(1));
+    }
+
+    #[test]
+    fn rejects_bad_cron() {
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            [[rules]]
+            cron = "every tuesday"
+            type = "page"
+            name = "dashboard"
+            duration = "1h"
+            "#,
+        )
+        .unwrap();
+        assert!(Scheduler::new(config, chrono_tz::Europe::London).is_err());
+    }
+
+    #[test]
+    fn keeps_match_day_within_budget() {
+        let start: DateTime<Utc> = "2025-01-10T15:00:00Z".parse().unwrap();
+        let mut budget = RefreshBudget::default();
+        let mut now = start;
+        for _ in 0..3 {
+            budget.record(now);
+            now = budget.next_allowed(now, Duration::from_secs(60), 3);
+        }
+        // three refreshes a minute apart use up the hour, so the next waits for the first to age
+        assert_eq!(now, start + TimeDelta::hours(1));
+
+        budget.record(now);
+        assert_eq!(
+            budget.next_allowed(now, Duration::from_secs(60), 3),
+            start + TimeDelta::minutes(61)
+        );
+
+        assert_eq!(
+            budget.next_allowed(now, Duration::MAX, 3),
+            DateTime::<Utc>::MAX_UTC
+        );
+    }
+
+    #[test]
+    fn rejects_zero_refresh_budget() {
+        let config: ScheduleConfig = toml::from_str(
+            r#"
+            [match_day]
+            max_refreshes_per_hour = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.validate().len(), 1);
+    }
+}
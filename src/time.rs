@@ -0,0 +1,105 @@
+use chrono::{DateTime, Locale, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+
+/// Timezone and locale that every date and time is shown in, whatever the host is set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Localization {
+    pub tz: Tz,
+    pub locale: Locale,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self {
+            tz: chrono_tz::Europe::London,
+            locale: Locale::en_GB,
+        }
+    }
+}
+
+impl Localization {
+    #[must_use]
+    pub fn now(&self) -> DateTime<Tz> {
+        Utc::now().with_timezone(&self.tz)
+    }
+
+    #[must_use]
+    pub fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+
+    /// Formats a time in the configured timezone, with day and month names in the locale.
+    #[must_use]
+    pub fn format<Z: TimeZone>(&self, time: &DateTime<Z>, fmt: &str) -> String {
+        time.with_timezone(&self.tz)
+            .format_localized(fmt, self.locale)
+            .to_string()
+    }
+
+    /// Formats a calendar date, with day and month names in the locale.
+    #[must_use]
+    pub fn format_date(&self, date: NaiveDate, fmt: &str) -> String {
+        date.format_localized(fmt, self.locale).to_string()
+    }
+}
+
+pub(crate) fn deserialize_tz<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tz, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse()
+        .map_err(|_| serde::de::Error::custom(format!("unknown timezone '{name}'")))
+}
+
+pub(crate) fn deserialize_locale<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Locale, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse()
+        .map_err(|_| serde::de::Error::custom(format!("unknown locale '{name}'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn follows_bst_transitions() {
+        let london = Localization::default();
+
+        // clocks go forward at 01:00 UTC on the last Sunday of March
+        assert_eq!(
+            london.format(&utc("2025-03-30T00:30:00Z"), "%H:%M %Z"),
+            "00:30 GMT"
+        );
+        assert_eq!(
+            london.format(&utc("2025-03-30T01:30:00Z"), "%H:%M %Z"),
+            "02:30 BST"
+        );
+
+        // and back at 01:00 UTC on the last Sunday of October
+        assert_eq!(
+            london.format(&utc("2025-10-26T00:30:00Z"), "%H:%M %Z"),
+            "01:30 BST"
+        );
+        assert_eq!(
+            london.format(&utc("2025-10-26T01:30:00Z"), "%H:%M %Z"),
+            "01:30 GMT"
+        );
+    }
+
+    #[test]
+    fn uses_locale_names() {
+        let paris = Localization {
+            tz: chrono_tz::Europe::Paris,
+            locale: Locale::fr_FR,
+        };
+        assert_eq!(
+            paris.format(&utc("2025-07-14T20:00:00Z"), "%a %d %b %H:%M"),
+            "lun. 14 juil. 22:00"
+        );
+    }
+}